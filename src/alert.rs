@@ -0,0 +1,733 @@
+//!
+//! Alert rules read from a config file and checked against the latest bar of every symbol.
+//!
+
+use crate::error::{Error, Result};
+use crate::expr::Expr;
+use crate::indicators::{max, min, price_diff};
+use crate::model::{Bar, BarInterval};
+use crate::output::{Field, Record};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub mod sink;
+
+///
+/// What has to happen at the latest bar for a rule to trigger. Prices are adjusted closes.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// The price moves from at or below the level to above it
+    CrossAbove(f64),
+    /// The price moves from at or above the level to below it
+    CrossBelow(f64),
+    /// The price changed by at least `percent` over the last `bars` bars, or over the whole
+    /// period without `bars`. A negative `percent` asks for a fall by at least that much.
+    Change { percent: f64, bars: Option<usize> },
+    /// The price closes above the highest close of the weeks before
+    NewHigh { weeks: u32 },
+    /// The price closes below the lowest close of the weeks before
+    NewLow { weeks: u32 },
+    /// The fast simple moving average crosses the slow one, upwards if `above`
+    SmaCross {
+        fast: usize,
+        slow: usize,
+        above: bool,
+    },
+    /// A condition of the expression language that holds at the latest bar
+    Expr(Expr),
+}
+
+fn closes(bars: &[Bar]) -> Vec<f64> {
+    bars.iter().map(|b| b.adjclose).collect()
+}
+
+impl Condition {
+    ///
+    /// Whether the condition holds at the last of `bars`, which have to be sorted by timestamp.
+    ///
+    pub fn check(&self, bars: &[Bar]) -> bool {
+        let (last, before) = match bars.split_last() {
+            Some((last, before)) => (last.adjclose, before),
+            None => return false,
+        };
+        let previous = before.last().map(|b| b.adjclose);
+        match self {
+            Condition::CrossAbove(level) => previous.is_some_and(|p| p <= *level && last > *level),
+            Condition::CrossBelow(level) => previous.is_some_and(|p| p >= *level && last < *level),
+            Condition::Change { percent, bars: n } => {
+                let start = n.map_or(0, |n| bars.len().saturating_sub(n + 1));
+                if start + 1 >= bars.len() {
+                    return false;
+                }
+                let change = price_diff(&closes(&bars[start..])).map_or(0.0, |(_, c)| c * 100.0);
+                if *percent >= 0.0 {
+                    change >= *percent
+                } else {
+                    change <= *percent
+                }
+            }
+            Condition::NewHigh { weeks } | Condition::NewLow { weeks } => {
+                let since = bars[bars.len() - 1].timestamp - Duration::weeks(*weeks as i64);
+                // a shorter history cannot tell whether the price is at a high of all the weeks
+                if bars[0].timestamp > since {
+                    return false;
+                }
+                let window: Vec<f64> = before
+                    .iter()
+                    .filter(|b| b.timestamp >= since)
+                    .map(|b| b.adjclose)
+                    .collect();
+                match self {
+                    Condition::NewHigh { .. } => max(&window).is_some_and(|high| last > high),
+                    _ => min(&window).is_some_and(|low| last < low),
+                }
+            }
+            Condition::SmaCross { .. } => self.to_expr().is_some_and(|e| last_signal(&e, bars)),
+            Condition::Expr(expr) => last_signal(expr, bars),
+        }
+    }
+
+    ///
+    /// The history the condition needs before the latest bar, with bars `bar` apart. A change
+    /// over the whole period needs none beyond the period.
+    ///
+    pub fn lookback(&self, bar: Duration) -> Duration {
+        match self {
+            Condition::CrossAbove(_) | Condition::CrossBelow(_) => bar,
+            Condition::Change { bars, .. } => bar * bars.unwrap_or(0) as i32,
+            Condition::NewHigh { weeks } | Condition::NewLow { weeks } => {
+                Duration::weeks(*weeks as i64)
+            }
+            Condition::SmaCross { .. } => {
+                self.to_expr().map_or(Duration::zero(), |e| e.lookback(bar))
+            }
+            Condition::Expr(expr) => expr.lookback(bar),
+        }
+    }
+
+    ///
+    /// The equivalent condition of the expression language, if there is one.
+    ///
+    fn to_expr(&self) -> Option<Expr> {
+        match self {
+            Condition::SmaCross { fast, slow, above } => Expr::condition(&format!(
+                "crosses_{}(sma({}), sma({}))",
+                if *above { "above" } else { "below" },
+                fast,
+                slow
+            ))
+            .ok(),
+            _ => None,
+        }
+    }
+}
+
+fn last_signal(expr: &Expr, bars: &[Bar]) -> bool {
+    expr.signals(bars).last().copied().unwrap_or(false)
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::CrossAbove(level) => write!(f, "crosses above {}", level),
+            Condition::CrossBelow(level) => write!(f, "crosses below {}", level),
+            Condition::Change { percent, bars } => {
+                let direction = if *percent >= 0.0 { "rises" } else { "falls" };
+                write!(f, "{} by {}% or more", direction, percent.abs())?;
+                match bars {
+                    Some(1) => write!(f, " in a bar"),
+                    Some(n) => write!(f, " over {} bars", n),
+                    None => write!(f, " over the period"),
+                }
+            }
+            Condition::NewHigh { weeks } => write!(f, "new {}-week high", weeks),
+            Condition::NewLow { weeks } => write!(f, "new {}-week low", weeks),
+            Condition::SmaCross { fast, slow, above } => write!(
+                f,
+                "sma({}) crosses {} sma({})",
+                fast,
+                if *above { "above" } else { "below" },
+                slow
+            ),
+            Condition::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+///
+/// A condition watched on one symbol, or on every symbol without one.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    /// Defaults to the description of the condition
+    pub name: String,
+    pub symbol: Option<String>,
+    pub condition: Condition,
+}
+
+impl AlertRule {
+    pub fn applies_to(&self, symbol: &str) -> bool {
+        self.symbol.as_deref().is_none_or(|s| s == symbol)
+    }
+}
+
+///
+/// A rule that triggered at a bar.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub name: String,
+    /// The description of the condition
+    pub condition: String,
+    pub price: f64,
+}
+
+impl Record for Alert {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("timestamp".to_string(), Field::Timestamp(self.timestamp)),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("alert".to_string(), Field::Text(self.name.clone())),
+            ("condition".to_string(), Field::Text(self.condition.clone())),
+            ("price".to_string(), Field::Number(Some(self.price))),
+        ]
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} at {}", self.name, self.symbol, self.price)?;
+        if self.name != self.condition {
+            write!(f, " ({})", self.condition)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Checks rules against growing histories, reporting every rule only once per symbol and bar.
+///
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    /// Where changes over the whole period start, when the histories reach back further
+    period_start: Option<DateTime<Utc>>,
+    /// The rule index, symbol and bar of every alert reported so far
+    seen: HashSet<(usize, String, DateTime<Utc>)>,
+}
+
+impl Alerts {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Alerts {
+            rules,
+            period_start: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    ///
+    /// Measures changes over the whole period from `from` instead of the first bar, for
+    /// histories fetched with the lookback of the rules.
+    ///
+    pub fn with_period_start(mut self, from: DateTime<Utc>) -> Self {
+        self.period_start = Some(from);
+        self
+    }
+
+    ///
+    /// Checks the rules of `symbol` at its last bar.
+    ///
+    /// # Returns
+    /// The alerts that were not reported for the same bar before, in the order of the rules.
+    ///
+    pub fn check(&mut self, symbol: &str, bars: &[Bar]) -> Vec<Alert> {
+        let last = match bars.last() {
+            Some(last) => last,
+            None => return Vec::new(),
+        };
+        let period = match self.period_start {
+            Some(from) => &bars[bars.partition_point(|b| b.timestamp < from)..],
+            None => bars,
+        };
+        let mut alerts = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let bars = match rule.condition {
+                Condition::Change { bars: None, .. } => period,
+                _ => bars,
+            };
+            if !rule.applies_to(symbol) || !rule.condition.check(bars) {
+                continue;
+            }
+            if self.seen.insert((i, symbol.to_string(), last.timestamp)) {
+                alerts.push(Alert {
+                    timestamp: last.timestamp,
+                    symbol: symbol.to_string(),
+                    name: rule.name.clone(),
+                    condition: rule.condition.to_string(),
+                    price: last.adjclose,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+///
+/// Where triggered alerts are sent.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    /// The output of the command, stdout unless a file is given with `--output`
+    Stdout,
+    /// Appends one JSON object per alert to a file
+    File { path: PathBuf },
+    /// POSTs every alert to a Slack or Teams compatible webhook
+    Webhook { url: String },
+    /// Emails every alert through an SMTP server at `host:port`
+    Smtp {
+        server: String,
+        from: String,
+        to: Vec<String>,
+    },
+    /// Runs a command for every alert, see `ExecNotifier`
+    Exec { command: Vec<String> },
+}
+
+fn default_weeks() -> u32 {
+    52
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    #[default]
+    Above,
+    Below,
+}
+
+///
+/// One rule as written in a config file, see `AlertConfig::from_toml`.
+///
+#[derive(Debug, Deserialize)]
+struct RuleRow {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    symbol: Option<String>,
+    #[serde(flatten)]
+    condition: ConditionRow,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ConditionRow {
+    CrossAbove {
+        level: f64,
+    },
+    CrossBelow {
+        level: f64,
+    },
+    Change {
+        percent: f64,
+        #[serde(default)]
+        bars: Option<usize>,
+    },
+    NewHigh {
+        #[serde(default = "default_weeks")]
+        weeks: u32,
+    },
+    NewLow {
+        #[serde(default = "default_weeks")]
+        weeks: u32,
+    },
+    SmaCross {
+        fast: usize,
+        slow: usize,
+        #[serde(default)]
+        direction: Direction,
+    },
+    Expr {
+        condition: String,
+    },
+}
+
+impl RuleRow {
+    fn into_rule(self, index: usize) -> Result<AlertRule> {
+        let invalid = |message: String| Error::Parse(format!("alert {}: {}", index + 1, message));
+        let condition = match self.condition {
+            ConditionRow::CrossAbove { level } => Condition::CrossAbove(level),
+            ConditionRow::CrossBelow { level } => Condition::CrossBelow(level),
+            ConditionRow::Change { percent, bars } => {
+                if percent == 0.0 || bars == Some(0) {
+                    return Err(invalid(
+                        "a change needs a percent other than 0 and at least 1 bar".to_string(),
+                    ));
+                }
+                Condition::Change { percent, bars }
+            }
+            ConditionRow::NewHigh { weeks } | ConditionRow::NewLow { weeks } if weeks == 0 => {
+                return Err(invalid("weeks has to be positive".to_string()))
+            }
+            ConditionRow::NewHigh { weeks } => Condition::NewHigh { weeks },
+            ConditionRow::NewLow { weeks } => Condition::NewLow { weeks },
+            ConditionRow::SmaCross {
+                fast,
+                slow,
+                direction,
+            } => {
+                if fast < 2 || fast >= slow {
+                    return Err(invalid(format!(
+                        "an sma cross needs 2 <= fast < slow, got {} and {}",
+                        fast, slow
+                    )));
+                }
+                Condition::SmaCross {
+                    fast,
+                    slow,
+                    above: matches!(direction, Direction::Above),
+                }
+            }
+            ConditionRow::Expr { condition } => {
+                Condition::Expr(Expr::condition(&condition).map_err(|e| invalid(e.to_string()))?)
+            }
+        };
+        let symbol = self
+            .symbol
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        Ok(AlertRule {
+            name: self.name.unwrap_or_else(|| condition.to_string()),
+            symbol,
+            condition,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlAlertConfig {
+    alerts: Vec<RuleRow>,
+    #[serde(default)]
+    sinks: Vec<SinkConfig>,
+}
+
+///
+/// The rules and sinks of an alerts config file.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertConfig {
+    pub rules: Vec<AlertRule>,
+    /// Never empty, defaults to stdout
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl AlertConfig {
+    pub fn load(path: &Path) -> Result<AlertConfig> {
+        AlertConfig::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    ///
+    /// Parses an `[[alerts]]` table per rule, with a `type` of `cross_above` or `cross_below`
+    /// and a `level`, `change` with a `percent` and optional `bars`, `new_high` or `new_low`
+    /// with optional `weeks` (52), `sma_cross` with `fast`, `slow` and an optional `direction`
+    /// (above or below) or `expr` with a `condition`. Every rule may have a `name` and a
+    /// `symbol`. `[[sinks]]` tables have a `type` of `stdout`, `file` with a `path`, `webhook`
+    /// with a `url`, `smtp` with a `server`, `from` and a list of recipients `to` or `exec` with
+    /// a `command` list.
+    ///
+    pub fn from_toml(s: &str) -> Result<AlertConfig> {
+        let file: TomlAlertConfig = toml::from_str(s).map_err(|e| Error::Parse(e.to_string()))?;
+        let rules = file
+            .alerts
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| row.into_rule(i))
+            .collect::<Result<_>>()?;
+        let sinks = if file.sinks.is_empty() {
+            vec![SinkConfig::Stdout]
+        } else {
+            file.sinks
+        };
+        Ok(AlertConfig { rules, sinks })
+    }
+
+    ///
+    /// The longest history any rule needs before the latest bar, for bars of `interval`.
+    ///
+    pub fn lookback(&self, interval: BarInterval) -> Duration {
+        let bar = interval.average_span();
+        self.rules
+            .iter()
+            .map(|r| r.condition.lookback(bar))
+            .max()
+            .unwrap_or_else(Duration::zero)
+    }
+
+    ///
+    /// The symbols the rules need: those named by rules, and `others` if a rule applies to
+    /// every symbol. Without duplicates, in the order they first appear.
+    ///
+    pub fn symbols(&self, others: &[String]) -> Vec<String> {
+        let mut symbols: Vec<String> = Vec::new();
+        let named = self.rules.iter().filter_map(|r| r.symbol.as_ref());
+        let unscoped = self.rules.iter().any(|r| r.symbol.is_none());
+        for symbol in named.chain(others.iter().filter(|_| unscoped)) {
+            if !symbols.contains(symbol) {
+                symbols.push(symbol.clone());
+            }
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap()
+                    + Duration::weeks(i as i64),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_conditions() {
+        let rising = bars(&[10.0, 12.0, 9.0, 11.0]);
+        let falling = bars(&[10.0, 12.0, 11.0, 9.5]);
+        let check = |condition: Condition, bars: &[Bar]| condition.check(bars);
+
+        assert!(check(Condition::CrossAbove(10.0), &rising));
+        assert!(!check(Condition::CrossAbove(11.0), &rising));
+        assert!(check(Condition::CrossBelow(10.0), &falling));
+        assert!(!check(Condition::CrossBelow(10.0), &rising[..1]));
+
+        let change = |percent, bars| Condition::Change { percent, bars };
+        assert!(check(change(10.0, None), &rising));
+        assert!(!check(change(10.0, Some(2)), &rising));
+        assert!(check(change(20.0, Some(1)), &rising));
+        assert!(check(change(-10.0, Some(1)), &falling));
+        assert!(!check(change(-10.0, Some(5)), &falling[..1]));
+
+        // the high of the weeks before the last bar is 12
+        assert!(!check(Condition::NewHigh { weeks: 3 }, &rising));
+        assert!(check(Condition::NewHigh { weeks: 1 }, &rising));
+        assert!(check(Condition::NewLow { weeks: 3 }, &falling));
+        assert!(!check(Condition::NewLow { weeks: 3 }, &falling[..1]));
+        // four weeks of history cannot make a 52-week low
+        assert!(!check(Condition::NewLow { weeks: 52 }, &falling));
+
+        let sma = Condition::SmaCross {
+            fast: 2,
+            slow: 3,
+            above: false,
+        };
+        // sma_2: -, 11, 11.5, 10.25; sma_3: -, -, 11, 10.83
+        assert!(check(sma, &falling));
+        let expr = Condition::Expr("close < prev(close) and volume == 0".parse().unwrap());
+        assert!(check(expr.clone(), &falling));
+        assert!(!check(expr, &rising));
+    }
+
+    #[test]
+    fn test_lookback() {
+        let day = Duration::days(1);
+        let lookback = |condition: Condition| condition.lookback(day).num_days();
+        assert_eq!(lookback(Condition::CrossAbove(10.0)), 1);
+        let change = |bars| Condition::Change { percent: 5.0, bars };
+        assert_eq!(lookback(change(Some(20))), 20);
+        assert_eq!(lookback(change(None)), 0);
+        assert_eq!(lookback(Condition::NewHigh { weeks: 52 }), 364);
+        let sma = Condition::SmaCross {
+            fast: 50,
+            slow: 200,
+            above: true,
+        };
+        // the slow average and the bar before the cross
+        assert_eq!(lookback(sma), 201);
+        let expr = |s: &str| Condition::Expr(s.parse().unwrap());
+        assert_eq!(lookback(expr("prev(macd(12, 26, 9), 5) > 0")), 40);
+        assert_eq!(lookback(expr("sma(30d) > close or rsi(14) < 30")), 30);
+
+        let config = AlertConfig {
+            rules: vec![
+                AlertRule {
+                    name: "low".to_string(),
+                    symbol: None,
+                    condition: Condition::NewLow { weeks: 8 },
+                },
+                AlertRule {
+                    name: "ema".to_string(),
+                    symbol: None,
+                    condition: expr("ema(20) > close"),
+                },
+            ],
+            sinks: vec![SinkConfig::Stdout],
+        };
+        assert_eq!(config.lookback(BarInterval::Day), Duration::weeks(8));
+        assert_eq!(config.lookback(BarInterval::Week).num_days(), 140);
+    }
+
+    #[test]
+    fn test_change_over_the_period() {
+        let history = bars(&[20.0, 10.0, 11.0]);
+        let rule = AlertRule {
+            name: "rule".to_string(),
+            symbol: None,
+            condition: Condition::Change {
+                percent: 5.0,
+                bars: None,
+            },
+        };
+        assert!(Alerts::new(vec![rule.clone()])
+            .check("MSFT", &history)
+            .is_empty());
+        // the first bar only belongs to the lookback
+        let mut alerts = Alerts::new(vec![rule]).with_period_start(history[1].timestamp);
+        assert_eq!(alerts.check("MSFT", &history).len(), 1);
+    }
+
+    #[test]
+    fn test_alerts_report_once_per_bar() {
+        let rule = |symbol: Option<&str>, condition| AlertRule {
+            name: "rule".to_string(),
+            symbol: symbol.map(String::from),
+            condition,
+        };
+        let mut alerts = Alerts::new(vec![
+            rule(Some("MSFT"), Condition::CrossAbove(10.0)),
+            rule(
+                None,
+                Condition::Change {
+                    percent: 5.0,
+                    bars: Some(1),
+                },
+            ),
+        ]);
+        let mut history = bars(&[10.0, 12.0]);
+
+        let triggered = alerts.check("MSFT", &history);
+        assert_eq!(triggered.len(), 2);
+        assert_eq!(triggered[0].price, 12.0);
+        assert_eq!(
+            triggered[1].to_string(),
+            "rule: MSFT at 12 (rises by 5% or more in a bar)"
+        );
+        assert_eq!(alerts.check("IBM", &history).len(), 1);
+        assert!(alerts.check("MSFT", &history).is_empty());
+
+        history.push(bars(&[0.0, 0.0, 13.0]).pop().unwrap());
+        let triggered = alerts.check("MSFT", &history);
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].timestamp, history[2].timestamp);
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = AlertConfig::from_toml(
+            r#"
+            [[alerts]]
+            symbol = "MSFT"
+            type = "cross_above"
+            level = 250
+
+            [[alerts]]
+            name = "golden cross"
+            type = "sma_cross"
+            fast = 50
+            slow = 200
+
+            [[alerts]]
+            symbol = "SPY"
+            type = "new_low"
+
+            [[alerts]]
+            type = "expr"
+            condition = "rsi(14) < 30"
+
+            [[sinks]]
+            type = "webhook"
+            url = "http://localhost:8080/hook"
+
+            [[sinks]]
+            type = "exec"
+            command = ["notify-send", "{subject}", "{body}"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.rules[0],
+            AlertRule {
+                name: "crosses above 250".to_string(),
+                symbol: Some("MSFT".to_string()),
+                condition: Condition::CrossAbove(250.0),
+            }
+        );
+        assert_eq!(config.rules[1].name, "golden cross");
+        assert_eq!(
+            config.rules[1].condition.to_string(),
+            "sma(50) crosses above sma(200)"
+        );
+        assert_eq!(config.rules[2].condition, Condition::NewLow { weeks: 52 });
+        assert_eq!(config.rules[3].name, "rsi(14) < 30");
+        assert_eq!(
+            config.sinks,
+            vec![
+                SinkConfig::Webhook {
+                    url: "http://localhost:8080/hook".to_string()
+                },
+                SinkConfig::Exec {
+                    command: vec![
+                        "notify-send".to_string(),
+                        "{subject}".to_string(),
+                        "{body}".to_string()
+                    ]
+                }
+            ]
+        );
+        assert_eq!(
+            config.symbols(&["AAPL".to_string(), "MSFT".to_string()]),
+            vec!["MSFT", "SPY", "AAPL"]
+        );
+    }
+
+    #[test]
+    fn test_from_toml_errors() {
+        let parse = |rule: &str| AlertConfig::from_toml(&format!("[[alerts]]\n{}", rule));
+
+        assert_eq!(
+            parse("type = \"cross_above\"\nlevel = 1").unwrap().sinks,
+            vec![SinkConfig::Stdout]
+        );
+        assert!(matches!(
+            parse("type = \"cross_above\""),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(parse("type = \"crossing\""), Err(Error::Parse(_))));
+        assert!(matches!(
+            parse("type = \"sma_cross\"\nfast = 50\nslow = 20"),
+            Err(Error::Parse(_))
+        ));
+        match parse("type = \"expr\"\ncondition = \"close >\"") {
+            Err(Error::Parse(message)) => assert!(message.starts_with("alert 1: expected a value")),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
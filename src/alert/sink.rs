@@ -0,0 +1,181 @@
+//!
+//! The destinations triggered alerts are sent to.
+//!
+
+use super::{Alert, SinkConfig};
+use crate::error::Result;
+use crate::notify::exec::ExecNotifier;
+use crate::notify::smtp::SmtpNotifier;
+use crate::notify::webhook::WebhookNotifier;
+use crate::notify::{Notification, Notifier};
+use crate::output::{create_writer, OutputFormat, RecordWriter};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::Duration;
+
+pub trait AlertSink {
+    fn send(&mut self, alert: &Alert) -> Result<()>;
+
+    ///
+    /// Writes out buffered alerts.
+    ///
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+///
+/// Writes alerts as records to the output of the command.
+///
+pub struct WriterSink<'a> {
+    writer: &'a mut dyn RecordWriter,
+}
+
+impl<'a> WriterSink<'a> {
+    pub fn new(writer: &'a mut dyn RecordWriter) -> Self {
+        WriterSink { writer }
+    }
+}
+
+impl AlertSink for WriterSink<'_> {
+    fn send(&mut self, alert: &Alert) -> Result<()> {
+        self.writer.write(alert)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+///
+/// Appends one JSON object per alert to a file, keeping the alerts of earlier runs.
+///
+pub struct FileSink {
+    writer: Box<dyn RecordWriter>,
+}
+
+impl FileSink {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink {
+            writer: create_writer(OutputFormat::Ndjson, Box::new(file))?,
+        })
+    }
+}
+
+impl AlertSink for FileSink {
+    fn send(&mut self, alert: &Alert) -> Result<()> {
+        self.writer.write(alert)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+///
+/// Hands every alert to a notifier, with the symbol and name of the alert as the subject.
+///
+pub struct NotifierSink {
+    notifier: Box<dyn Notifier>,
+}
+
+impl NotifierSink {
+    pub fn new(notifier: Box<dyn Notifier>) -> Self {
+        NotifierSink { notifier }
+    }
+}
+
+impl AlertSink for NotifierSink {
+    fn send(&mut self, alert: &Alert) -> Result<()> {
+        self.notifier.notify(&notification(alert))
+    }
+}
+
+fn notification(alert: &Alert) -> Notification {
+    Notification {
+        subject: format!("{}: {}", alert.symbol, alert.name),
+        body: format!("{} on {}", alert, alert.timestamp.to_rfc3339()),
+    }
+}
+
+///
+/// Creates the sinks of a config. Every `stdout` sink writes to `output`, which only receives
+/// each alert once even if the config lists it several times.
+///
+pub fn create_sinks<'a>(
+    configs: &[SinkConfig],
+    output: &'a mut dyn RecordWriter,
+    timeout: Duration,
+) -> Result<Vec<Box<dyn AlertSink + 'a>>> {
+    let mut output = Some(output);
+    let mut sinks: Vec<Box<dyn AlertSink + 'a>> = Vec::new();
+    for config in configs {
+        match config {
+            SinkConfig::Stdout => {
+                if let Some(output) = output.take() {
+                    sinks.push(Box::new(WriterSink::new(output)));
+                }
+            }
+            SinkConfig::File { path } => sinks.push(Box::new(FileSink::open(path)?)),
+            SinkConfig::Webhook { url } => sinks.push(Box::new(NotifierSink::new(Box::new(
+                WebhookNotifier::new(url, timeout)?,
+            )))),
+            SinkConfig::Smtp { server, from, to } => sinks.push(Box::new(NotifierSink::new(
+                Box::new(SmtpNotifier::new(server, from, to, timeout)?),
+            ))),
+            SinkConfig::Exec { command } => sinks.push(Box::new(NotifierSink::new(Box::new(
+                ExecNotifier::new(command)?,
+            )))),
+        }
+    }
+    Ok(sinks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn alert(symbol: &str) -> Alert {
+        Alert {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap(),
+            symbol: symbol.to_string(),
+            name: "breakout".to_string(),
+            condition: "crosses above 250".to_string(),
+            price: 251.5,
+        }
+    }
+
+    #[test]
+    fn test_notification() {
+        assert_eq!(
+            notification(&alert("MSFT")),
+            Notification {
+                subject: "MSFT: breakout".to_string(),
+                body: "breakout: MSFT at 251.5 (crosses above 250) on 2021-01-04T00:00:00+00:00"
+                    .to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_sink_appends() {
+        let path = std::env::temp_dir().join(format!(
+            "stock-tracker-alerts-{}.ndjson",
+            std::process::id()
+        ));
+        for symbol in ["MSFT", "IBM"].iter() {
+            let mut sink = FileSink::open(&path).unwrap();
+            sink.send(&alert(symbol)).unwrap();
+            sink.finish().unwrap();
+        }
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""symbol":"MSFT""#));
+        assert!(lines[1].contains(r#""symbol":"IBM""#));
+    }
+}
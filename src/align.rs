@@ -0,0 +1,134 @@
+//!
+//! Lines up the histories of several symbols on the timestamps, or days, they have in common.
+//!
+
+use crate::model::Bar;
+use chrono::prelude::*;
+
+///
+/// The adjusted closes of several symbols on their common timestamps.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedCloses {
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// One series per symbol, in the order the symbols were given, each as long as `timestamps`
+    pub closes: Vec<Vec<f64>>,
+}
+
+///
+/// Keeps the bars of every series whose timestamp occurs in all of them. Every series has to be
+/// sorted by timestamp.
+///
+pub fn align_closes(series: &[&[Bar]]) -> AlignedCloses {
+    align_closes_by(series, |t| t)
+}
+
+///
+/// Keeps the bars of every series whose `key` occurs in all of them, e.g. the day of the
+/// timestamp to line up daily bars of exchanges opening at different times. `key` has to keep
+/// the order of the timestamps, and every series may only have one bar per key. The aligned
+/// timestamps are the keys.
+///
+pub fn align_closes_by<F>(series: &[&[Bar]], key: F) -> AlignedCloses
+where
+    F: Fn(DateTime<Utc>) -> DateTime<Utc>,
+{
+    let mut aligned = AlignedCloses {
+        timestamps: Vec::new(),
+        closes: vec![Vec::new(); series.len()],
+    };
+    if series.is_empty() {
+        return aligned;
+    }
+
+    let mut positions = vec![0; series.len()];
+    loop {
+        // the latest of the current timestamps is the earliest one that can be common
+        let mut latest = None;
+        for (bars, position) in series.iter().zip(&positions) {
+            match bars.get(*position) {
+                Some(bar) => latest = latest.max(Some(key(bar.timestamp))),
+                None => return aligned,
+            }
+        }
+        let latest = latest.expect("at least one series");
+
+        let mut common = true;
+        for (bars, position) in series.iter().zip(positions.iter_mut()) {
+            while bars
+                .get(*position)
+                .is_some_and(|b| key(b.timestamp) < latest)
+            {
+                *position += 1;
+            }
+            common &= bars
+                .get(*position)
+                .is_some_and(|b| key(b.timestamp) == latest);
+        }
+
+        if common {
+            aligned.timestamps.push(latest);
+            for ((bars, position), closes) in series
+                .iter()
+                .zip(positions.iter_mut())
+                .zip(&mut aligned.closes)
+            {
+                closes.push(bars[*position].adjclose);
+                *position += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(days: &[u32]) -> Vec<Bar> {
+        days.iter()
+            .map(|day| Bar {
+                timestamp: Utc.with_ymd_and_hms(2021, 1, *day, 0, 0, 0).unwrap(),
+                open: 0.0,
+                high: 0.0,
+                low: 0.0,
+                close: *day as f64,
+                adjclose: *day as f64,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_align_closes() {
+        let a = bars(&[1, 2, 4, 5, 7]);
+        let b = bars(&[2, 3, 4, 6, 7]);
+        let c = bars(&[1, 2, 3, 4, 7, 8]);
+        let aligned = align_closes(&[&a, &b, &c]);
+
+        let days: Vec<u32> = aligned.timestamps.iter().map(|t| t.day()).collect();
+        assert_eq!(days, vec![2, 4, 7]);
+        assert_eq!(aligned.closes, vec![vec![2.0, 4.0, 7.0]; 3]);
+
+        assert!(align_closes(&[&a, &[]]).timestamps.is_empty());
+        assert_eq!(align_closes(&[]).closes, Vec::<Vec<f64>>::new());
+    }
+
+    #[test]
+    fn test_align_closes_by_day() {
+        let a = bars(&[1, 2, 4]);
+        let later: Vec<Bar> = bars(&[2, 3, 4])
+            .into_iter()
+            .map(|b| Bar {
+                timestamp: b.timestamp + chrono::Duration::hours(9),
+                ..b
+            })
+            .collect();
+        assert!(align_closes(&[&a, &later]).timestamps.is_empty());
+
+        let day =
+            |t: DateTime<Utc>| Utc.from_utc_datetime(&t.date_naive().and_time(NaiveTime::MIN));
+        let aligned = align_closes_by(&[&a, &later], day);
+        assert_eq!(aligned.timestamps, vec![a[1].timestamp, a[2].timestamp]);
+        assert_eq!(aligned.closes, vec![vec![2.0, 4.0]; 2]);
+    }
+}
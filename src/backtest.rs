@@ -0,0 +1,453 @@
+//!
+//! Simulates trading strategies over the history of a symbol.
+//!
+
+use crate::model::Bar;
+use crate::output::{Field, Record};
+use crate::risk::{max_drawdown, sharpe_ratio, simple_returns, RiskOptions};
+use chrono::prelude::*;
+use std::str::FromStr;
+
+pub mod strategy;
+
+use strategy::Strategy;
+
+///
+/// The account and the costs of trading.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BacktestOptions {
+    /// The cash the account starts with
+    pub capital: f64,
+    /// A fixed cost per fill
+    pub commission: f64,
+    /// How much worse than the open every fill is, as a fraction of the price
+    pub slippage: f64,
+    pub risk: RiskOptions,
+}
+
+impl Default for BacktestOptions {
+    fn default() -> Self {
+        BacktestOptions {
+            capital: 10_000.0,
+            commission: 0.0,
+            slippage: 0.0,
+            risk: RiskOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BacktestReport {
+    /// One row per symbol and strategy with the performance metrics
+    Summary,
+    /// One row per bar with the account's value
+    Equity,
+    /// One row per round trip
+    Trades,
+}
+
+impl FromStr for BacktestReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "summary" => Ok(BacktestReport::Summary),
+            "equity" => Ok(BacktestReport::Equity),
+            "trades" => Ok(BacktestReport::Trades),
+            _ => Err(format!(
+                "unknown report '{}', expected summary, equity or trades",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// The account after the close of a bar.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquityPoint {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub strategy: String,
+    /// The adjusted close
+    pub price: f64,
+    pub shares: f64,
+    pub cash: f64,
+    pub equity: f64,
+    /// The decline from the highest equity so far, as a fraction
+    pub drawdown: f64,
+}
+
+impl Record for EquityPoint {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("timestamp".to_string(), Field::Timestamp(self.timestamp)),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("strategy".to_string(), Field::Text(self.strategy.clone())),
+            ("price".to_string(), Field::Number(Some(self.price))),
+            ("shares".to_string(), Field::Number(Some(self.shares))),
+            ("cash".to_string(), Field::Number(Some(self.cash))),
+            ("equity".to_string(), Field::Number(Some(self.equity))),
+            (
+                "drawdown_pct".to_string(),
+                Field::Number(Some(self.drawdown * 100.0)),
+            ),
+        ]
+    }
+}
+
+///
+/// A round trip: shares bought and sold again, or still held and valued at the last close.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub symbol: String,
+    pub strategy: String,
+    /// closed or open
+    pub status: &'static str,
+    pub entry: DateTime<Utc>,
+    /// The fill price including slippage
+    pub entry_price: f64,
+    /// The exit, or the last bar for open trades
+    pub exit: DateTime<Utc>,
+    pub exit_price: f64,
+    pub shares: f64,
+    /// The profit after slippage and the commissions of both fills
+    pub pnl: f64,
+    /// The profit relative to the cash spent on the entry, as a fraction
+    pub return_: f64,
+}
+
+impl Record for Trade {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("strategy".to_string(), Field::Text(self.strategy.clone())),
+            ("status".to_string(), Field::Text(self.status.to_string())),
+            ("entry".to_string(), Field::Timestamp(self.entry)),
+            (
+                "entry_price".to_string(),
+                Field::Number(Some(self.entry_price)),
+            ),
+            ("exit".to_string(), Field::Timestamp(self.exit)),
+            (
+                "exit_price".to_string(),
+                Field::Number(Some(self.exit_price)),
+            ),
+            ("shares".to_string(), Field::Number(Some(self.shares))),
+            ("pnl".to_string(), Field::Number(Some(self.pnl))),
+            (
+                "return_pct".to_string(),
+                Field::Number(Some(self.return_ * 100.0)),
+            ),
+        ]
+    }
+}
+
+///
+/// The performance of a strategy over the whole history.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestSummary {
+    pub symbol: String,
+    pub strategy: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub capital: f64,
+    pub final_equity: f64,
+    /// All following figures are fractions
+    pub total_return: f64,
+    /// The compound annual growth rate, `None` for periods without duration or a lost account
+    pub cagr: Option<f64>,
+    /// The return of holding the symbol without trading costs, for comparison
+    pub market_return: f64,
+    pub max_drawdown: f64,
+    pub sharpe: Option<f64>,
+    pub trades: usize,
+    /// The share of closed trades with a profit, `None` without closed trades
+    pub win_rate: Option<f64>,
+    /// The share of bars the strategy held shares after the close
+    pub exposure: f64,
+}
+
+impl Record for BacktestSummary {
+    fn fields(&self) -> Vec<(String, Field)> {
+        let pct = |value: f64| Field::Number(Some(value * 100.0));
+        vec![
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("strategy".to_string(), Field::Text(self.strategy.clone())),
+            ("start".to_string(), Field::Timestamp(self.start)),
+            ("end".to_string(), Field::Timestamp(self.end)),
+            ("capital".to_string(), Field::Number(Some(self.capital))),
+            (
+                "final_equity".to_string(),
+                Field::Number(Some(self.final_equity)),
+            ),
+            ("total_return_pct".to_string(), pct(self.total_return)),
+            (
+                "cagr_pct".to_string(),
+                Field::Number(self.cagr.map(|c| c * 100.0)),
+            ),
+            ("market_return_pct".to_string(), pct(self.market_return)),
+            ("max_drawdown_pct".to_string(), pct(self.max_drawdown)),
+            ("sharpe".to_string(), Field::Number(self.sharpe)),
+            (
+                "trades".to_string(),
+                Field::Number(Some(self.trades as f64)),
+            ),
+            (
+                "win_rate_pct".to_string(),
+                Field::Number(self.win_rate.map(|w| w * 100.0)),
+            ),
+            ("exposure_pct".to_string(), pct(self.exposure)),
+        ]
+    }
+}
+
+///
+/// The outcome of running a strategy over a history.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backtest {
+    pub summary: BacktestSummary,
+    pub equity: Vec<EquityPoint>,
+    pub trades: Vec<Trade>,
+}
+
+///
+/// The shares held between an entry and its exit.
+///
+struct Position {
+    entry: DateTime<Utc>,
+    entry_price: f64,
+    shares: f64,
+    /// The cash spent, including the commission
+    cost: f64,
+}
+
+impl Backtest {
+    ///
+    /// Runs `strategy` over `bars`, sorted by timestamp, on adjusted prices so that splits and
+    /// dividends do not look like price moves.
+    ///
+    /// The strategy decides at each close and orders are filled at the next bar's open, worse
+    /// by the slippage and charged the commission. Entries invest all cash, exits sell all
+    /// shares. A position still held after the last bar is reported as an open trade.
+    ///
+    /// # Returns
+    /// `None` if there are no bars.
+    ///
+    pub fn run(
+        symbol: &str,
+        bars: &[Bar],
+        strategy: &Strategy,
+        options: &BacktestOptions,
+    ) -> Option<Backtest> {
+        let (first, last) = (bars.first()?, bars.last()?);
+        let adjusted: Vec<Bar> = bars.iter().map(Bar::adjusted).collect();
+        let targets = strategy.positions(bars);
+        let name = strategy.to_string();
+
+        let mut cash = options.capital;
+        let mut position: Option<Position> = None;
+        let mut trades = Vec::new();
+        let mut equity: Vec<EquityPoint> = Vec::with_capacity(bars.len());
+        let mut peak = f64::MIN;
+        let trade = |position: &Position, status, exit, exit_price, proceeds: f64| Trade {
+            symbol: symbol.to_string(),
+            strategy: name.clone(),
+            status,
+            entry: position.entry,
+            entry_price: position.entry_price,
+            exit,
+            exit_price,
+            shares: position.shares,
+            pnl: proceeds - position.cost,
+            return_: proceeds / position.cost - 1.0,
+        };
+
+        for (i, bar) in adjusted.iter().enumerate() {
+            let wanted = i > 0 && targets[i - 1];
+            match position.take() {
+                Some(held) if !wanted => {
+                    let price = bar.open * (1.0 - options.slippage);
+                    let proceeds = held.shares * price - options.commission;
+                    trades.push(trade(&held, "closed", bar.timestamp, price, proceeds));
+                    cash += proceeds;
+                }
+                None if wanted && cash > options.commission && bar.open > 0.0 => {
+                    let price = bar.open * (1.0 + options.slippage);
+                    position = Some(Position {
+                        entry: bar.timestamp,
+                        entry_price: price,
+                        shares: (cash - options.commission) / price,
+                        cost: cash,
+                    });
+                    cash = 0.0;
+                }
+                held => position = held,
+            }
+
+            let shares = position.as_ref().map_or(0.0, |p| p.shares);
+            let value = cash + shares * bar.close;
+            peak = peak.max(value);
+            equity.push(EquityPoint {
+                timestamp: bar.timestamp,
+                symbol: symbol.to_string(),
+                strategy: name.clone(),
+                price: bar.close,
+                shares,
+                cash,
+                equity: value,
+                drawdown: if peak > 0.0 { 1.0 - value / peak } else { 0.0 },
+            });
+        }
+
+        let last_close = adjusted[adjusted.len() - 1].close;
+        if let Some(held) = &position {
+            trades.push(trade(
+                held,
+                "open",
+                last.timestamp,
+                last_close,
+                held.shares * last_close,
+            ));
+        }
+
+        let values: Vec<f64> = equity.iter().map(|e| e.equity).collect();
+        let final_equity = values[values.len() - 1];
+        let closed: Vec<&Trade> = trades.iter().filter(|t| t.status == "closed").collect();
+        let years = (last.timestamp - first.timestamp).num_seconds() as f64 / (365.25 * 86_400.0);
+        let growth = final_equity / options.capital;
+
+        let summary = BacktestSummary {
+            symbol: symbol.to_string(),
+            strategy: name.clone(),
+            start: first.timestamp,
+            end: last.timestamp,
+            capital: options.capital,
+            final_equity,
+            total_return: growth - 1.0,
+            cagr: if years > 0.0 && growth > 0.0 {
+                Some(growth.powf(1.0 / years) - 1.0)
+            } else {
+                None
+            },
+            market_return: last.adjclose / first.adjclose - 1.0,
+            max_drawdown: max_drawdown(&values).map_or(0.0, |d| d.depth),
+            sharpe: sharpe_ratio(
+                &simple_returns(&values),
+                options.risk.risk_free_rate,
+                options.risk.periods_per_year,
+            ),
+            trades: trades.len(),
+            win_rate: if closed.is_empty() {
+                None
+            } else {
+                Some(closed.iter().filter(|t| t.pnl > 0.0).count() as f64 / closed.len() as f64)
+            },
+            exposure: equity.iter().filter(|e| e.shares > 0.0).count() as f64 / equity.len() as f64,
+        };
+
+        Some(Backtest {
+            summary,
+            equity,
+            trades,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(prices: &[(f64, f64)]) -> Vec<Bar> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, (open, close))| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, 1 + i as u32, 0, 0, 0)
+                    .unwrap(),
+                open: *open,
+                high: open.max(*close),
+                low: open.min(*close),
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buy_and_hold() {
+        let bars = bars(&[(10.0, 10.0), (10.0, 12.0), (12.0, 9.0), (9.0, 15.0)]);
+        let options = BacktestOptions {
+            capital: 1000.0,
+            commission: 10.0,
+            slippage: 0.01,
+            ..BacktestOptions::default()
+        };
+        let backtest = Backtest::run("MSFT", &bars, &Strategy::BuyAndHold, &options).unwrap();
+
+        // bought at the second open for 10.1 with 990 left after the commission
+        let shares = 990.0 / 10.1;
+        let equity: Vec<f64> = backtest.equity.iter().map(|e| e.equity).collect();
+        assert_eq!(equity[0], 1000.0);
+        assert!((equity[3] - shares * 15.0).abs() < 1e-9);
+        assert_eq!(backtest.trades.len(), 1);
+        assert_eq!(backtest.trades[0].status, "open");
+        assert!((backtest.trades[0].pnl - (shares * 15.0 - 1000.0)).abs() < 1e-9);
+
+        let summary = &backtest.summary;
+        assert!((summary.total_return - (shares * 15.0 / 1000.0 - 1.0)).abs() < 1e-12);
+        assert_eq!(summary.market_return, 0.5);
+        assert!((summary.max_drawdown - 0.25).abs() < 1e-12);
+        assert_eq!(summary.win_rate, None);
+        assert_eq!(summary.exposure, 0.75);
+        // three days are far less than a year, so the annual rate is huge
+        assert!(summary.cagr.unwrap() > 100.0);
+        assert_eq!(
+            Backtest::run("MSFT", &[], &Strategy::BuyAndHold, &options),
+            None
+        );
+    }
+
+    #[test]
+    fn test_round_trips() {
+        // sma_2 > sma_3 after the 5th and 6th close: bought at the 6th open, sold at the 8th
+        let bars = bars(&[
+            (5.0, 5.0),
+            (4.0, 4.0),
+            (3.0, 3.0),
+            (4.0, 4.0),
+            (6.0, 6.0),
+            (5.0, 5.0),
+            (3.0, 3.0),
+            (4.0, 4.0),
+        ]);
+        let strategy = Strategy::SmaCrossover { fast: 2, slow: 3 };
+        let backtest =
+            Backtest::run("MSFT", &bars, &strategy, &BacktestOptions::default()).unwrap();
+
+        assert_eq!(backtest.trades.len(), 1);
+        let trade = &backtest.trades[0];
+        assert_eq!(trade.status, "closed");
+        assert_eq!((trade.entry_price, trade.exit_price), (5.0, 4.0));
+        assert_eq!(trade.entry, bars[5].timestamp);
+        assert_eq!(trade.exit, bars[7].timestamp);
+        assert!((trade.return_ + 0.2).abs() < 1e-12);
+        assert_eq!(backtest.summary.win_rate, Some(0.0));
+        assert!((backtest.summary.final_equity - 8000.0).abs() < 1e-9);
+        assert_eq!(backtest.summary.exposure, 0.25);
+        assert_eq!(backtest.equity[7].shares, 0.0);
+    }
+
+    #[test]
+    fn test_parse_report() {
+        assert_eq!("Trades".parse(), Ok(BacktestReport::Trades));
+        assert!("lots".parse::<BacktestReport>().is_err());
+    }
+}
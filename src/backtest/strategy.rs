@@ -0,0 +1,270 @@
+//!
+//! The rules that decide when a backtest holds a position.
+//!
+
+use crate::expr::Expr;
+use crate::indicators::{Indicator, Window};
+use crate::model::Bar;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Strategy {
+    /// Long from the first bar to the last
+    BuyAndHold,
+    /// Long while the fast simple moving average is above the slow one
+    SmaCrossover { fast: usize, slow: usize },
+    /// Enters when the RSI falls below `lower` and exits when it rises above `upper`
+    RsiThreshold {
+        period: usize,
+        lower: f64,
+        upper: f64,
+    },
+    /// Long while the `entry` condition holds, or with an `exit` condition from a bar where
+    /// `entry` holds until one where `exit` does
+    Rule { entry: Expr, exit: Option<Expr> },
+}
+
+///
+/// The values of an indicator with a single column, `None` where there is not enough data.
+///
+fn column(indicator: Indicator, bars: &[Bar]) -> Vec<Option<f64>> {
+    indicator
+        .compute(bars)
+        .into_iter()
+        .next()
+        .map_or_else(|| vec![None; bars.len()], |(_, values)| values)
+}
+
+impl Strategy {
+    ///
+    /// Decides at the close of every bar whether to be long afterwards. Signals only use the
+    /// bars up to the one they belong to.
+    ///
+    pub fn positions(&self, bars: &[Bar]) -> Vec<bool> {
+        match self {
+            Strategy::BuyAndHold => vec![true; bars.len()],
+            Strategy::SmaCrossover { fast, slow } => {
+                let fast = column(Indicator::Sma(Window::Bars(*fast)), bars);
+                let slow = column(Indicator::Sma(Window::Bars(*slow)), bars);
+                fast.iter()
+                    .zip(&slow)
+                    .map(|pair| matches!(pair, (Some(fast), Some(slow)) if fast > slow))
+                    .collect()
+            }
+            Strategy::RsiThreshold {
+                period,
+                lower,
+                upper,
+            } => {
+                let mut long = false;
+                column(Indicator::Rsi(*period), bars)
+                    .into_iter()
+                    .map(|rsi| {
+                        match rsi {
+                            Some(rsi) if rsi < *lower => long = true,
+                            Some(rsi) if rsi > *upper => long = false,
+                            _ => {}
+                        }
+                        long
+                    })
+                    .collect()
+            }
+            Strategy::Rule { entry, exit: None } => entry.signals(bars),
+            Strategy::Rule {
+                entry,
+                exit: Some(exit),
+            } => {
+                let mut long = false;
+                entry
+                    .signals(bars)
+                    .into_iter()
+                    .zip(exit.signals(bars))
+                    .map(|(enter, exit)| {
+                        long = if long { !exit } else { enter };
+                        long
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::BuyAndHold => write!(f, "buy-and-hold"),
+            Strategy::SmaCrossover { fast, slow } => write!(f, "sma:{}:{}", fast, slow),
+            Strategy::RsiThreshold {
+                period,
+                lower,
+                upper,
+            } => write!(f, "rsi:{}:{}:{}", period, lower, upper),
+            Strategy::Rule { entry, exit: None } => write!(f, "{}", entry),
+            Strategy::Rule {
+                entry,
+                exit: Some(exit),
+            } => write!(f, "{} until {}", entry, exit),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    ///
+    /// Parses `buy-and-hold`, `sma:<fast>:<slow>` or `rsi:<period>:<lower>:<upper>`, e.g.
+    /// `sma:20:50` or `rsi:14:30:70`. The parameters of `rsi` default to that example.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let params: Vec<&str> = parts.collect();
+
+        match name.as_str() {
+            "buy-and-hold" | "hold" if params.is_empty() => Ok(Strategy::BuyAndHold),
+            "sma" => {
+                let windows = params
+                    .iter()
+                    .map(|p| p.parse::<usize>().ok().filter(|p| *p > 1))
+                    .collect::<Option<Vec<usize>>>();
+                match windows.as_deref() {
+                    Some([fast, slow]) if fast < slow => Ok(Strategy::SmaCrossover {
+                        fast: *fast,
+                        slow: *slow,
+                    }),
+                    _ => Err(
+                        "'sma' expects <fast>:<slow> bars with fast < slow, e.g. 'sma:20:50'"
+                            .to_string(),
+                    ),
+                }
+            }
+            "rsi" if params.is_empty() => Ok(Strategy::RsiThreshold {
+                period: 14,
+                lower: 30.0,
+                upper: 70.0,
+            }),
+            "rsi" => match params.as_slice() {
+                [period, lower, upper] => {
+                    let period = period.parse::<usize>().ok().filter(|p| *p > 1);
+                    let bounds = (lower.parse::<f64>(), upper.parse::<f64>());
+                    match (period, bounds) {
+                        (Some(period), (Ok(lower), Ok(upper)))
+                            if 0.0 <= lower && lower < upper && upper <= 100.0 =>
+                        {
+                            Ok(Strategy::RsiThreshold {
+                                period,
+                                lower,
+                                upper,
+                            })
+                        }
+                        _ => Err(format!("invalid rsi strategy '{}', expected a period above 1 and thresholds with 0 <= lower < upper <= 100", s)),
+                    }
+                }
+                _ => Err("'rsi' expects <period>:<lower>:<upper>, e.g. 'rsi:14:30:70'".to_string()),
+            },
+            _ => Err(format!(
+                "unknown strategy '{}', expected buy-and-hold, sma:<fast>:<slow> or rsi:<period>:<lower>:<upper>",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, 1 + i as u32, 0, 0, 0)
+                    .unwrap(),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!("buy-and-hold".parse(), Ok(Strategy::BuyAndHold));
+        assert_eq!(
+            "SMA:20:50".parse(),
+            Ok(Strategy::SmaCrossover { fast: 20, slow: 50 })
+        );
+        assert_eq!(
+            "rsi".parse(),
+            Ok(Strategy::RsiThreshold {
+                period: 14,
+                lower: 30.0,
+                upper: 70.0
+            })
+        );
+        let rsi: Strategy = "rsi:7:20:80".parse().unwrap();
+        assert_eq!(rsi.to_string(), "rsi:7:20:80");
+        for invalid in ["sma:50:20", "sma:20", "rsi:14:70:30", "hold:1", "macd"].iter() {
+            assert!(invalid.parse::<Strategy>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_sma_crossover_positions() {
+        let bars = bars(&[5.0, 4.0, 3.0, 4.0, 6.0, 5.0, 3.0]);
+        let positions = Strategy::SmaCrossover { fast: 2, slow: 3 }.positions(&bars);
+        // sma_2: -, 4.5, 3.5, 3.5, 5, 5.5, 4; sma_3: -, -, 4, 3.67, 4.33, 5, 4.67
+        assert_eq!(
+            positions,
+            vec![false, false, false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_rsi_threshold_positions() {
+        let bars = bars(&[10.0, 9.0, 8.0, 9.0, 10.0, 11.0, 12.0, 11.0]);
+        let strategy = Strategy::RsiThreshold {
+            period: 2,
+            lower: 30.0,
+            upper: 70.0,
+        };
+        // rsi_2: -, -, 0, 50, 75, 87.5, 93.75, 46.88: entered at 0, left at 75
+        assert_eq!(
+            strategy.positions(&bars),
+            vec![false, false, true, true, false, false, false, false]
+        );
+        assert_eq!(Strategy::BuyAndHold.positions(&bars[..2]), vec![true; 2]);
+    }
+
+    #[test]
+    fn test_rule_positions() {
+        let bars = bars(&[10.0, 9.0, 8.0, 9.0, 10.0, 11.0, 12.0, 11.0]);
+        let rule = |entry: &str, exit: Option<&str>| Strategy::Rule {
+            entry: entry.parse().unwrap(),
+            exit: exit.map(|exit| exit.parse().unwrap()),
+        };
+        assert_eq!(
+            rule("close > prev(close)", None).positions(&bars),
+            vec![false, false, false, true, true, true, true, false]
+        );
+
+        // the same positions as the rsi strategy above
+        let strategy = rule("rsi(2) < 30", Some("rsi(2) > 70"));
+        assert_eq!(strategy.to_string(), "rsi(2) < 30 until rsi(2) > 70");
+        assert_eq!(
+            strategy.positions(&bars),
+            vec![false, false, true, true, false, false, false, false]
+        );
+        assert_eq!(
+            rule("close < 9", Some("close < 10")).positions(&bars),
+            vec![false, false, true, false, false, false, false, false]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//!
+//! Metrics of a symbol relative to a benchmark, e.g. an index, over the dates both were quoted.
+//!
+
+use crate::align::{align_closes, align_closes_by};
+use crate::correlation::{covariance, pearson};
+use crate::model::{Bar, BarInterval};
+use crate::output::{Field, Record};
+use crate::resample::bucket_start;
+use crate::risk::{mean, simple_returns, stdev, RiskOptions};
+
+///
+/// Calculate the beta of `returns` against `benchmark` returns: their covariance relative to
+/// the variance of the benchmark.
+///
+pub fn beta(returns: &[f64], benchmark: &[f64]) -> Option<f64> {
+    let variance = stdev(benchmark)?.powi(2);
+    if variance == 0.0 {
+        return None;
+    }
+    Some(covariance(returns, benchmark)? / variance)
+}
+
+///
+/// The metrics of a symbol against the benchmark. Metrics that need more aligned bars than
+/// available are `None`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkMetrics {
+    pub beta: Option<f64>,
+    /// Jensen's alpha, annualized, as a fraction
+    pub alpha: Option<f64>,
+    pub correlation: Option<f64>,
+    /// The annualized standard deviation of the difference in returns as a fraction
+    pub tracking_error: Option<f64>,
+    /// How much better the symbol did than the benchmark over the aligned period: the ratio of
+    /// their growth minus one, as a fraction
+    pub relative_performance: Option<f64>,
+}
+
+impl BenchmarkMetrics {
+    ///
+    /// Compares the adjusted closes of `bars` to those of `benchmark` on the (UTC) days both
+    /// have, as daily bars of exchanges in other time zones are stamped at other times of the
+    /// day. Intraday bars are compared on the timestamps both have. Both have to be sorted by
+    /// timestamp.
+    ///
+    pub fn from_bars(bars: &[Bar], benchmark: &[Bar], options: &RiskOptions) -> BenchmarkMetrics {
+        let day = |t| bucket_start(t, BarInterval::Day);
+        let intraday = [bars, benchmark].iter().any(|s| {
+            s.windows(2)
+                .any(|w| day(w[0].timestamp) == day(w[1].timestamp))
+        });
+        let aligned = if intraday {
+            align_closes(&[bars, benchmark])
+        } else {
+            align_closes_by(&[bars, benchmark], day)
+        };
+        let (closes, benchmark_closes) = (&aligned.closes[0], &aligned.closes[1]);
+        let returns = simple_returns(closes);
+        let benchmark_returns = simple_returns(benchmark_closes);
+        let differences: Vec<f64> = returns
+            .iter()
+            .zip(&benchmark_returns)
+            .map(|(r, b)| r - b)
+            .collect();
+
+        let periods = options.periods_per_year;
+        let risk_free = options.risk_free_rate / periods;
+        let beta = beta(&returns, &benchmark_returns);
+        let alpha = beta.and_then(|beta| {
+            let excess = mean(&returns)? - risk_free;
+            let benchmark_excess = mean(&benchmark_returns)? - risk_free;
+            Some((excess - beta * benchmark_excess) * periods)
+        });
+        let relative_performance = match (closes.first(), closes.last()) {
+            (Some(first), Some(last)) if closes.len() > 1 => {
+                let benchmark_growth = benchmark_closes[closes.len() - 1] / benchmark_closes[0];
+                Some(last / first / benchmark_growth - 1.0)
+            }
+            _ => None,
+        };
+
+        BenchmarkMetrics {
+            beta,
+            alpha,
+            correlation: pearson(&returns, &benchmark_returns),
+            tracking_error: stdev(&differences).map(|s| s * periods.sqrt()),
+            relative_performance,
+        }
+    }
+}
+
+impl Record for BenchmarkMetrics {
+    fn fields(&self) -> Vec<(String, Field)> {
+        let pct = |value: Option<f64>| Field::Number(value.map(|v| v * 100.0));
+        vec![
+            ("beta".to_string(), Field::Number(self.beta)),
+            ("alpha_pct".to_string(), pct(self.alpha)),
+            ("correlation".to_string(), Field::Number(self.correlation)),
+            ("tracking_error_pct".to_string(), pct(self.tracking_error)),
+            ("relative_pct".to_string(), pct(self.relative_performance)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(days: &[u32], closes: &[f64]) -> Vec<Bar> {
+        days.iter()
+            .zip(closes)
+            .map(|(day, close)| Bar {
+                timestamp: Utc.with_ymd_and_hms(2021, 1, *day, 0, 0, 0).unwrap(),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_beta() {
+        let benchmark = [0.01, -0.02, 0.03, 0.0];
+        let doubled: Vec<f64> = benchmark.iter().map(|r| r * 2.0).collect();
+        assert!((beta(&doubled, &benchmark).unwrap() - 2.0).abs() < 1e-12);
+        assert_eq!(beta(&doubled, &[0.0; 4]), None);
+    }
+
+    #[test]
+    fn test_benchmark_metrics() {
+        let benchmark = bars(&[1, 2, 3, 4, 5], &[100.0, 102.0, 99.0, 101.0, 104.0]);
+        // the symbol is missing the 3rd, so only four dates line up
+        let symbol = bars(&[1, 2, 4, 5, 6], &[10.0, 10.3, 10.2, 10.7, 11.0]);
+        let metrics = BenchmarkMetrics::from_bars(&symbol, &benchmark, &RiskOptions::default());
+
+        let expected_relative = (10.7 / 10.0) / (104.0 / 100.0) - 1.0;
+        assert!((metrics.relative_performance.unwrap() - expected_relative).abs() < 1e-12);
+        assert!((metrics.beta.unwrap() - 1.4502100405909857).abs() < 1e-9);
+        assert!((metrics.correlation.unwrap() - 0.9963844639933219).abs() < 1e-9);
+
+        let itself = BenchmarkMetrics::from_bars(&benchmark, &benchmark, &RiskOptions::default());
+        assert!((itself.beta.unwrap() - 1.0).abs() < 1e-12);
+        assert!(itself.alpha.unwrap().abs() < 1e-12);
+        assert_eq!(itself.tracking_error, Some(0.0));
+        assert_eq!(itself.relative_performance, Some(0.0));
+
+        // a Tokyo listing stamped at 00:00 UTC against a New York one stamped at 14:30 UTC
+        let new_york: Vec<Bar> = benchmark
+            .iter()
+            .map(|b| Bar {
+                timestamp: b.timestamp + chrono::Duration::minutes(14 * 60 + 30),
+                ..b.clone()
+            })
+            .collect();
+        let offset = BenchmarkMetrics::from_bars(&symbol, &new_york, &RiskOptions::default());
+        assert_eq!(offset, metrics);
+    }
+
+    #[test]
+    fn test_intraday_benchmark_metrics() {
+        // hourly bars from 14:00 on
+        let hourly = |closes: &[f64]| -> Vec<Bar> {
+            let mut bars = bars(&[4; 4], closes);
+            for (i, bar) in bars.iter_mut().enumerate() {
+                bar.timestamp += chrono::Duration::hours(14 + i as i64);
+            }
+            bars
+        };
+        let benchmark = hourly(&[100.0, 102.0, 99.0, 101.0]);
+        let mut symbol = hourly(&[10.0, 10.4, 0.0, 10.2]);
+        // the symbol is missing the bar at 16:00
+        symbol.remove(2);
+        let metrics = BenchmarkMetrics::from_bars(&symbol, &benchmark, &RiskOptions::default());
+        // the hours of the day line up, not the day
+        let expected_relative = (10.2 / 10.0) / (101.0 / 100.0) - 1.0;
+        assert!((metrics.relative_performance.unwrap() - expected_relative).abs() < 1e-12);
+        assert!(metrics.beta.is_some());
+    }
+}
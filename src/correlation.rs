@@ -0,0 +1,263 @@
+//!
+//! Pairwise co-movement of the returns of several symbols.
+//!
+
+use crate::align::align_closes;
+use crate::model::Bar;
+use crate::output::{Field, Record};
+use crate::risk::{mean, simple_returns, stdev};
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// Calculate the sample covariance of two series of the same length.
+///
+pub fn covariance(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() != b.len() || a.len() < 2 {
+        return None;
+    }
+    let (mean_a, mean_b) = (mean(a)?, mean(b)?);
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (x - mean_a) * (y - mean_b))
+        .sum();
+    Some(sum / (a.len() - 1) as f64)
+}
+
+///
+/// Calculate the Pearson correlation of two series of the same length.
+///
+pub fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let deviations = stdev(a)? * stdev(b)?;
+    if deviations == 0.0 {
+        return None;
+    }
+    Some(covariance(a, b)? / deviations)
+}
+
+///
+/// Calculate the Spearman rank correlation of two series of the same length: the Pearson
+/// correlation of their ranks.
+///
+pub fn spearman(a: &[f64], b: &[f64]) -> Option<f64> {
+    pearson(&ranks(a), &ranks(b))
+}
+
+///
+/// Ranks the values of `series` starting at 1. Equal values get the average of their ranks.
+///
+pub fn ranks(series: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..series.len()).collect();
+    order.sort_by(|a, b| {
+        series[*a]
+            .partial_cmp(&series[*b])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut ranks = vec![0.0; series.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && series[order[end]] == series[order[start]] {
+            end += 1;
+        }
+        // ranks start + 1 ..= end share their average
+        let rank = (start + 1 + end) as f64 / 2.0;
+        for i in &order[start..end] {
+            ranks[*i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Measure {
+    Pearson,
+    Spearman,
+    Covariance,
+}
+
+impl Measure {
+    pub fn compute(&self, a: &[f64], b: &[f64]) -> Option<f64> {
+        match self {
+            Measure::Pearson => pearson(a, b),
+            Measure::Spearman => spearman(a, b),
+            Measure::Covariance => covariance(a, b),
+        }
+    }
+}
+
+impl fmt::Display for Measure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Measure::Pearson => write!(f, "pearson"),
+            Measure::Spearman => write!(f, "spearman"),
+            Measure::Covariance => write!(f, "covariance"),
+        }
+    }
+}
+
+impl FromStr for Measure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pearson" => Ok(Measure::Pearson),
+            "spearman" => Ok(Measure::Spearman),
+            "covariance" | "cov" => Ok(Measure::Covariance),
+            _ => Err(format!(
+                "unknown measure '{}', expected pearson, spearman or covariance",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// One row of a correlation matrix: the measure of `symbol` against every symbol.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrelationRow {
+    pub measure: Measure,
+    pub symbol: String,
+    /// The value against every symbol, named after it. `None` if there are too few common
+    /// returns or a series does not vary.
+    pub values: Vec<(String, Option<f64>)>,
+}
+
+impl Record for CorrelationRow {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("measure".to_string(), Field::Text(self.measure.to_string())),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+        ]
+        .into_iter()
+        .chain(
+            self.values
+                .iter()
+                .map(|(name, value)| (name.clone(), Field::Number(*value))),
+        )
+        .collect()
+    }
+}
+
+///
+/// Computes the matrix of every measure over the returns of the adjusted closes, aligned on the
+/// timestamps all symbols have in common. `histories` holds the bars of every symbol, each sorted
+/// by timestamp.
+///
+/// # Returns
+/// One row per measure and symbol, grouped by measure, and the number of common returns.
+///
+pub fn correlation_matrix(
+    histories: &[(String, Vec<Bar>)],
+    measures: &[Measure],
+) -> (Vec<CorrelationRow>, usize) {
+    let series: Vec<&[Bar]> = histories.iter().map(|(_, bars)| bars.as_slice()).collect();
+    let returns: Vec<Vec<f64>> = align_closes(&series)
+        .closes
+        .iter()
+        .map(|closes| simple_returns(closes))
+        .collect();
+
+    let mut rows = Vec::new();
+    for measure in measures {
+        for ((symbol, _), a) in histories.iter().zip(&returns) {
+            rows.push(CorrelationRow {
+                measure: *measure,
+                symbol: symbol.clone(),
+                values: histories
+                    .iter()
+                    .zip(&returns)
+                    .map(|((other, _), b)| (other.clone(), measure.compute(a, b)))
+                    .collect(),
+            });
+        }
+    }
+    let observations = returns.first().map_or(0, Vec::len);
+    (rows, observations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(days: &[u32], closes: &[f64]) -> Vec<Bar> {
+        days.iter()
+            .zip(closes)
+            .map(|(day, close)| Bar {
+                timestamp: Utc.with_ymd_and_hms(2021, 1, *day, 0, 0, 0).unwrap(),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pearson_and_covariance() {
+        let a = [0.01, -0.02, 0.03, 0.0];
+        let doubled: Vec<f64> = a.iter().map(|r| r * 2.0).collect();
+        let inverse: Vec<f64> = a.iter().map(|r| -r).collect();
+        assert!((pearson(&doubled, &a).unwrap() - 1.0).abs() < 1e-12);
+        assert!((pearson(&inverse, &a).unwrap() + 1.0).abs() < 1e-12);
+        assert!((covariance(&a, &a).unwrap() - stdev(&a).unwrap().powi(2)).abs() < 1e-15);
+        assert_eq!(pearson(&a, &[0.0; 4]), None);
+        assert_eq!(covariance(&a, &[0.0; 3]), None);
+    }
+
+    #[test]
+    fn test_ranks() {
+        assert_eq!(ranks(&[3.0, 1.0, 2.0]), vec![3.0, 1.0, 2.0]);
+        assert_eq!(ranks(&[1.0, 5.0, 1.0, 0.0]), vec![2.5, 4.0, 2.5, 1.0]);
+        assert_eq!(ranks(&[]), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn test_spearman() {
+        // monotonic but not linear
+        let a = [1.0, 2.0, 3.0, 4.0];
+        let b = [1.0, 8.0, 27.0, 64.0];
+        assert!((spearman(&a, &b).unwrap() - 1.0).abs() < 1e-12);
+        assert!(pearson(&a, &b).unwrap() < 1.0);
+        assert!((spearman(&[1.0, 2.0, 3.0], &[1.0, 3.0, 2.0]).unwrap() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_correlation_matrix() {
+        let histories = vec![
+            (
+                "A".to_string(),
+                bars(&[1, 2, 3, 4], &[10.0, 11.0, 10.0, 12.0]),
+            ),
+            // missing the 3rd, so its returns are taken over the 2nd to the 4th
+            (
+                "B".to_string(),
+                bars(&[1, 2, 4, 5], &[20.0, 22.0, 24.0, 30.0]),
+            ),
+        ];
+        let (rows, observations) =
+            correlation_matrix(&histories, &[Measure::Pearson, Measure::Covariance]);
+
+        assert_eq!(observations, 2);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1].measure, Measure::Pearson);
+        assert_eq!(rows[1].symbol, "B");
+        let b_with_a = rows[1].values[0].1.unwrap();
+        assert!((b_with_a - 1.0).abs() < 1e-12);
+        assert_eq!(rows[2].measure, Measure::Covariance);
+        assert_eq!(
+            rows[2].fields()[2..]
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["A", "B"]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//!
+//! The error type shared by providers and the command line tool.
+//!
+
+use std::fmt;
+use std::io;
+use yahoo_finance_api::YahooError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The provider could not be reached or did not answer in time
+    Network(String),
+    /// Data or input that could not be parsed
+    Parse(String),
+    /// The provider does not know the symbol
+    UnknownSymbol(String),
+    /// The provider knows the symbol but has no quotes for the requested period
+    EmptyRange(String),
+    /// Any other failure reported by a provider
+    Provider(String),
+    /// Invalid options, e.g. a provider without the settings it requires
+    Config(String),
+    /// Reading or writing local files failed
+    Io(io::Error),
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(cause) => write!(f, "network error: {}", cause),
+            Self::Parse(cause) => write!(f, "parse error: {}", cause),
+            Self::UnknownSymbol(symbol) => write!(f, "unknown symbol '{}'", symbol),
+            Self::EmptyRange(symbol) => write!(
+                f,
+                "no quotes found for '{}' in the requested period",
+                symbol
+            ),
+            Self::Provider(cause) => write!(f, "provider error: {}", cause),
+            Self::Config(cause) => write!(f, "invalid configuration: {}", cause),
+            Self::Io(e) => write!(f, "i/o error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        let message = e.to_string();
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => Self::Io(e),
+            _ => Self::Parse(message),
+        }
+    }
+}
+
+impl From<YahooError> for Error {
+    fn from(e: YahooError) -> Self {
+        match e {
+            YahooError::ConnectionFailed | YahooError::FetchFailed(_) => {
+                Self::Network(e.to_string())
+            }
+            YahooError::DeserializeFailed(_)
+            | YahooError::InvalidJson
+            | YahooError::DataInconsistency => Self::Parse(e.to_string()),
+            YahooError::EmptyDataSet => Self::Provider(e.to_string()),
+        }
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Self::Provider(format!("fetch task failed: {}", e))
+    }
+}
@@ -0,0 +1,298 @@
+//!
+//! Dividends and splits: listing them and the income they add to the price return.
+//!
+
+use crate::model::{Bar, Event, EventKind};
+use crate::output::{Field, Record};
+use chrono::prelude::*;
+use chrono::Duration;
+
+///
+/// A corporate action of a symbol, as printed by the `events` subcommand.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRow {
+    pub symbol: String,
+    pub event: Event,
+}
+
+impl Record for EventRow {
+    fn fields(&self) -> Vec<(String, Field)> {
+        let (kind, amount, ratio) = match self.event.kind {
+            EventKind::Dividend(amount) => ("dividend", Some(amount), None),
+            EventKind::Split(ratio) => ("split", None, Some(ratio)),
+        };
+        vec![
+            (
+                "timestamp".to_string(),
+                Field::Timestamp(self.event.timestamp),
+            ),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("type".to_string(), Field::Text(kind.to_string())),
+            ("amount".to_string(), Field::Number(amount)),
+            ("ratio".to_string(), Field::Number(ratio)),
+        ]
+    }
+}
+
+///
+/// The product of the ratios of all splits after `timestamp` up to and including `until`: what
+/// an amount per share at `timestamp` has to be divided by to be per share at `until`.
+///
+fn later_splits(events: &[Event], timestamp: DateTime<Utc>, until: DateTime<Utc>) -> f64 {
+    events
+        .iter()
+        .filter(|e| e.timestamp > timestamp && e.timestamp <= until)
+        .filter_map(|e| match e.kind {
+            EventKind::Split(ratio) => Some(ratio),
+            EventKind::Dividend(_) => None,
+        })
+        .product()
+}
+
+///
+/// Sums the dividends per share with an ex-date after `from` up to and including `to`, in
+/// shares as of `to`.
+///
+pub fn dividends_between(events: &[Event], from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    events
+        .iter()
+        .filter(|e| e.timestamp > from && e.timestamp <= to)
+        .filter_map(|e| match e.kind {
+            EventKind::Dividend(amount) => Some(amount / later_splits(events, e.timestamp, to)),
+            EventKind::Split(_) => None,
+        })
+        // folded from +0.0, as an empty float sum is -0.0
+        .fold(0.0, |sum, amount| sum + amount)
+}
+
+///
+/// Calculates the total return of holding one share from the first to the last bar, with
+/// every dividend reinvested at the close of its ex-date. `bars` and `events` have to be sorted
+/// by timestamp, and the bars have to carry traded closes, so that splits show up in the price.
+///
+/// # Returns
+/// The return as a fraction, `None` if there are less than two bars or the first close is not
+/// positive.
+///
+pub fn total_return(bars: &[Bar], events: &[Event]) -> Option<f64> {
+    let (first, last) = (bars.first()?, bars.last()?);
+    if bars.len() < 2 || first.close <= 0.0 {
+        return None;
+    }
+
+    let mut shares = 1.0;
+    let mut pending = events
+        .iter()
+        .skip_while(|e| e.timestamp <= first.timestamp)
+        .peekable();
+    for bar in &bars[1..] {
+        while let Some(event) = pending.next_if(|e| e.timestamp <= bar.timestamp) {
+            match event.kind {
+                EventKind::Split(ratio) => shares *= ratio,
+                EventKind::Dividend(amount) if bar.close > 0.0 => {
+                    shares += shares * amount / bar.close
+                }
+                EventKind::Dividend(_) => {}
+            }
+        }
+    }
+    Some(shares * last.close / first.close - 1.0)
+}
+
+///
+/// Recomputes the adjusted closes of `bars` from their traded closes and the `events` of the
+/// same symbol, both sorted by timestamp, relative to the last bar: a split divides every
+/// earlier close by its ratio and a dividend scales every earlier close by one minus the
+/// dividend over the close before its ex-date. Events after the last bar are ignored, so the
+/// result only depends on the period itself.
+///
+pub fn adjust(bars: &[Bar], events: &[Event]) -> Vec<Bar> {
+    let last = match bars.last() {
+        Some(last) => last.timestamp,
+        None => return Vec::new(),
+    };
+
+    let mut adjusted = bars.to_vec();
+    let mut factor = 1.0;
+    let mut pending = events
+        .iter()
+        .rev()
+        .skip_while(|e| e.timestamp > last)
+        .peekable();
+    for bar in adjusted.iter_mut().rev() {
+        while let Some(event) = pending.next_if(|e| e.timestamp > bar.timestamp) {
+            match event.kind {
+                EventKind::Split(ratio) => factor /= ratio,
+                EventKind::Dividend(amount) if amount < bar.close => {
+                    factor *= 1.0 - amount / bar.close
+                }
+                EventKind::Dividend(_) => {}
+            }
+        }
+        bar.adjclose = bar.close * factor;
+    }
+    adjusted
+}
+
+///
+/// The income side of a symbol's return over a period.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct DividendMetrics {
+    /// The dividends per share paid over the period, in shares as of its end
+    pub dividends: f64,
+    /// The dividends of the twelve months up to the last bar relative to its close, as a
+    /// fraction
+    pub dividend_yield: Option<f64>,
+    /// The return with dividends reinvested, as a fraction
+    pub total_return: Option<f64>,
+}
+
+impl DividendMetrics {
+    ///
+    /// Computes the metrics of `bars` from the `events` of the same symbol, both sorted by
+    /// timestamp. The trailing yield only counts what `events` covers, so they should reach
+    /// back a year before the last bar.
+    ///
+    /// # Returns
+    /// `None` if there are no bars.
+    ///
+    pub fn from_bars(bars: &[Bar], events: &[Event]) -> Option<DividendMetrics> {
+        let (first, last) = (bars.first()?, bars.last()?);
+        let trailing =
+            dividends_between(events, last.timestamp - Duration::days(365), last.timestamp);
+        Some(DividendMetrics {
+            dividends: dividends_between(events, first.timestamp, last.timestamp),
+            dividend_yield: if last.close > 0.0 {
+                Some(trailing / last.close)
+            } else {
+                None
+            },
+            total_return: total_return(bars, events),
+        })
+    }
+}
+
+impl Record for DividendMetrics {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("dividends".to_string(), Field::Number(Some(self.dividends))),
+            (
+                "dividend_yield_pct".to_string(),
+                Field::Number(self.dividend_yield.map(|y| y * 100.0)),
+            ),
+            (
+                "total_return_pct".to_string(),
+                Field::Number(self.total_return.map(|r| r * 100.0)),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, month, day, 0, 0, 0).unwrap()
+    }
+
+    fn bar(timestamp: DateTime<Utc>, close: f64) -> Bar {
+        Bar {
+            timestamp,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            adjclose: close,
+            volume: 0,
+        }
+    }
+
+    fn event(timestamp: DateTime<Utc>, kind: EventKind) -> Event {
+        Event { timestamp, kind }
+    }
+
+    #[test]
+    fn test_total_return() {
+        let bars = vec![
+            bar(day(1, 4), 100.0),
+            bar(day(1, 5), 100.0),
+            bar(day(1, 6), 50.0),
+            bar(day(1, 7), 55.0),
+        ];
+        let events = vec![
+            // before the first bar, so not received
+            event(day(1, 1), EventKind::Dividend(10.0)),
+            event(day(1, 5), EventKind::Dividend(10.0)),
+            event(day(1, 6), EventKind::Split(2.0)),
+        ];
+
+        // 1.1 shares after the dividend, 2.2 after the split, worth 121 at the end
+        let total = total_return(&bars, &events).unwrap();
+        assert!((total - 0.21).abs() < 1e-12);
+        assert!((total_return(&bars, &[]).unwrap() + 0.45).abs() < 1e-12);
+        assert_eq!(total_return(&bars[..1], &events), None);
+    }
+
+    #[test]
+    fn test_adjust() {
+        let bars = vec![
+            bar(day(1, 4), 100.0),
+            bar(day(1, 5), 100.0),
+            bar(day(1, 6), 50.0),
+            bar(day(1, 7), 55.0),
+        ];
+        let events = vec![
+            event(day(1, 1), EventKind::Split(3.0)),
+            event(day(1, 5), EventKind::Dividend(10.0)),
+            event(day(1, 6), EventKind::Split(2.0)),
+            // after the last bar
+            event(day(1, 8), EventKind::Dividend(5.0)),
+        ];
+
+        let adjclose: Vec<f64> = adjust(&bars, &events).iter().map(|b| b.adjclose).collect();
+        assert_eq!(adjclose, vec![45.0, 50.0, 50.0, 55.0]);
+        let closes: Vec<f64> = adjust(&bars, &events).iter().map(|b| b.close).collect();
+        assert_eq!(closes, vec![100.0, 100.0, 50.0, 55.0]);
+        assert!(adjust(&[], &events).is_empty());
+    }
+
+    #[test]
+    fn test_dividend_metrics() {
+        let bars = vec![bar(day(6, 1), 40.0), bar(day(12, 1), 25.0)];
+        let events = vec![
+            // more than a year before the last bar
+            event(day(1, 1) - Duration::days(365), EventKind::Dividend(9.0)),
+            event(day(3, 1), EventKind::Dividend(1.0)),
+            event(day(6, 1), EventKind::Dividend(1.0)),
+            event(day(9, 1), EventKind::Split(2.0)),
+            event(day(11, 1), EventKind::Dividend(0.5)),
+        ];
+        let metrics = DividendMetrics::from_bars(&bars, &events).unwrap();
+
+        // the dividend on the first bar's day is before the period, but within the year
+        assert_eq!(metrics.dividends, 0.5);
+        assert_eq!(metrics.dividend_yield, Some(0.06));
+        // 2 shares after the split and 2.04 after the dividend, worth 51 at the end
+        assert!((metrics.total_return.unwrap() - 0.275).abs() < 1e-12);
+        assert_eq!(DividendMetrics::from_bars(&[], &events), None);
+
+        let none = DividendMetrics::from_bars(&bars, &[]).unwrap();
+        assert_eq!(none.fields()[0].1, Field::Number(Some(0.0)));
+        assert_eq!(none.dividend_yield, Some(0.0));
+    }
+
+    #[test]
+    fn test_event_row() {
+        let row = EventRow {
+            symbol: "AAPL".to_string(),
+            event: event(day(8, 31), EventKind::Split(4.0)),
+        };
+        let fields = row.fields();
+        assert_eq!(fields[2].1, Field::Text("split".to_string()));
+        assert_eq!(fields[3].1, Field::Number(None));
+        assert_eq!(fields[4].1, Field::Number(Some(4.0)));
+    }
+}
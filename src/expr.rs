@@ -0,0 +1,481 @@
+//!
+//! A small expression language for signals over the bars of a symbol, so that strategies and
+//! alerts can be defined without recompiling, e.g. `sma(20) > sma(50) and rsi(14) < 30`.
+//!
+//! Expressions combine
+//! - the bar fields `open`, `high`, `low`, `close` (the adjusted close, also `price`) and
+//!   `volume`, and number literals,
+//! - indicator calls taking the parameters of the matching `--indicators` column, e.g. `sma(20)`,
+//!   `sma(30d)`, `ema(12)`, `rsi(14)`, `macd(12, 26, 9)`, `macd_signal()`, `macd_hist()`,
+//!   `bb_upper(20, 2)`, `bb_middle()`, `bb_lower()`, `tr()`, `atr(14)`, `stoch_k(14, 3)`,
+//!   `stoch_d()`, `vwap()`, `obv()` and `adv(20)`,
+//! - `abs(x)`, `prev(x)` or `prev(x, n)` for the value `n` bars earlier, and
+//!   `crosses_above(a, b)` and `crosses_below(a, b)`, which hold on the bar where `a` moves to
+//!   the other side of `b`,
+//! - arithmetic `+ - * /`, comparisons `< <= > >= == !=`, `and`, `or`, `not` and parentheses.
+//!
+//! Values are unknown while an indicator lacks data. Arithmetic and comparisons on unknown
+//! values are unknown, `and` and `or` only when the known side does not decide them.
+//!
+
+use crate::indicators::Indicator;
+use crate::model::Bar;
+use chrono::Duration;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod parser;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Number,
+    Bool,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "a number"),
+            Type::Bool => write!(f, "a condition"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarField {
+    Open,
+    High,
+    Low,
+    /// The adjusted close
+    Close,
+    Volume,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+///
+/// A node of a parsed and type checked expression.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Number(f64),
+    Bool(bool),
+    Field(BarField),
+    /// One column of an indicator's output
+    Indicator {
+        indicator: Indicator,
+        column: usize,
+    },
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Abs(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    /// Holds where `left` moves from at or below `right` to above it, or the reverse
+    Cross {
+        above: bool,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    /// The value a number of bars earlier
+    Prev(Box<Node>, usize),
+}
+
+///
+/// The value of an expression at every bar, `None` where it is unknown.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    Number(Vec<Option<f64>>),
+    Bool(Vec<Option<bool>>),
+}
+
+impl Values {
+    fn numbers(self) -> Vec<Option<f64>> {
+        match self {
+            Values::Number(values) => values,
+            Values::Bool(values) => values
+                .into_iter()
+                .map(|v| v.map(|b| if b { 1.0 } else { 0.0 }))
+                .collect(),
+        }
+    }
+
+    fn bools(self) -> Vec<Option<bool>> {
+        match self {
+            Values::Bool(values) => values,
+            Values::Number(values) => values.into_iter().map(|v| v.map(|n| n != 0.0)).collect(),
+        }
+    }
+}
+
+///
+/// A syntax or type error, pointing at the offending part of the expression.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    /// The byte offset in `source` the error refers to
+    pub position: usize,
+    pub source: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.source[..self.position.min(self.source.len())]
+            .chars()
+            .count();
+        write!(
+            f,
+            "{} at column {}\n    {}\n    {}^",
+            self.message,
+            column + 1,
+            self.source,
+            " ".repeat(column)
+        )
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+///
+/// A parsed expression with the text it was parsed from.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    source: String,
+    root: Node,
+    kind: Type,
+}
+
+impl Expr {
+    ///
+    /// Parses and type checks `source`, which may be a number or a condition.
+    ///
+    pub fn parse(source: &str) -> Result<Expr, ExprError> {
+        let (root, kind) = parser::parse(source)?;
+        Ok(Expr {
+            source: source.trim().to_string(),
+            root,
+            kind,
+        })
+    }
+
+    ///
+    /// Parses `source` like `parse`, but only accepts conditions.
+    ///
+    pub fn condition(source: &str) -> Result<Expr, ExprError> {
+        let expr = Expr::parse(source)?;
+        if expr.kind != Type::Bool {
+            return Err(ExprError {
+                message: "expected a condition like 'close > sma(20)', not a number".to_string(),
+                position: source.len() - source.trim_start().len(),
+                source: source.to_string(),
+            });
+        }
+        Ok(expr)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn kind(&self) -> Type {
+        self.kind
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    ///
+    /// Evaluates the expression at every bar of `bars`, which have to be sorted by timestamp.
+    ///
+    pub fn evaluate(&self, bars: &[Bar]) -> Values {
+        evaluate(&self.root, bars)
+    }
+
+    ///
+    /// Whether the condition holds at every bar, counting unknown values as not holding.
+    ///
+    pub fn signals(&self, bars: &[Bar]) -> Vec<bool> {
+        self.evaluate(bars)
+            .bools()
+            .into_iter()
+            .map(|v| v.unwrap_or(false))
+            .collect()
+    }
+
+    ///
+    /// The history needed before the expression has a value, with bars `bar` apart.
+    ///
+    pub fn lookback(&self, bar: Duration) -> Duration {
+        lookback(&self.root, bar)
+    }
+}
+
+fn lookback(node: &Node, bar: Duration) -> Duration {
+    match node {
+        Node::Number(_) | Node::Bool(_) | Node::Field(_) => Duration::zero(),
+        Node::Indicator { indicator, .. } => indicator.lookback(bar),
+        Node::Neg(inner) | Node::Not(inner) | Node::Abs(inner) => lookback(inner, bar),
+        Node::Binary(_, left, right) => lookback(left, bar).max(lookback(right, bar)),
+        Node::Cross { left, right, .. } => lookback(left, bar).max(lookback(right, bar)) + bar,
+        Node::Prev(inner, n) => lookback(inner, bar) + bar * *n as i32,
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    ///
+    /// Parses a condition, see `Expr::condition`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expr::condition(s).map_err(|e| e.to_string())
+    }
+}
+
+fn zip_with<A: Copy, B: Copy, T>(
+    left: Vec<Option<A>>,
+    right: Vec<Option<B>>,
+    f: impl Fn(A, B) -> Option<T>,
+) -> Vec<Option<T>> {
+    left.into_iter()
+        .zip(right)
+        .map(|(a, b)| f(a?, b?))
+        .collect()
+}
+
+fn evaluate(node: &Node, bars: &[Bar]) -> Values {
+    let field = |f: fn(&Bar) -> f64| Values::Number(bars.iter().map(|b| Some(f(b))).collect());
+    match node {
+        Node::Number(n) => Values::Number(vec![Some(*n); bars.len()]),
+        Node::Bool(b) => Values::Bool(vec![Some(*b); bars.len()]),
+        Node::Field(BarField::Open) => field(|b| b.adjusted().open),
+        Node::Field(BarField::High) => field(|b| b.adjusted().high),
+        Node::Field(BarField::Low) => field(|b| b.adjusted().low),
+        Node::Field(BarField::Close) => field(|b| b.adjclose),
+        Node::Field(BarField::Volume) => field(|b| b.volume as f64),
+        Node::Indicator { indicator, column } => Values::Number(
+            indicator
+                .compute(bars)
+                .into_iter()
+                .nth(*column)
+                .map_or_else(|| vec![None; bars.len()], |(_, values)| values),
+        ),
+        Node::Neg(inner) => Values::Number(
+            evaluate(inner, bars)
+                .numbers()
+                .into_iter()
+                .map(|v| v.map(|n| -n))
+                .collect(),
+        ),
+        Node::Abs(inner) => Values::Number(
+            evaluate(inner, bars)
+                .numbers()
+                .into_iter()
+                .map(|v| v.map(f64::abs))
+                .collect(),
+        ),
+        Node::Not(inner) => Values::Bool(
+            evaluate(inner, bars)
+                .bools()
+                .into_iter()
+                .map(|v| v.map(|b| !b))
+                .collect(),
+        ),
+        Node::Binary(op, left, right) => {
+            let (left, right) = (evaluate(left, bars), evaluate(right, bars));
+            match op {
+                BinaryOp::And | BinaryOp::Or => {
+                    let decisive = *op == BinaryOp::Or;
+                    Values::Bool(
+                        left.bools()
+                            .into_iter()
+                            .zip(right.bools())
+                            .map(|pair| match pair {
+                                (Some(a), _) if a == decisive => Some(decisive),
+                                (_, Some(b)) if b == decisive => Some(decisive),
+                                (Some(_), Some(_)) => Some(!decisive),
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
+                _ => {
+                    let (left, right) = (left.numbers(), right.numbers());
+                    match op {
+                        BinaryOp::Add => Values::Number(zip_with(left, right, |a, b| Some(a + b))),
+                        BinaryOp::Sub => Values::Number(zip_with(left, right, |a, b| Some(a - b))),
+                        BinaryOp::Mul => Values::Number(zip_with(left, right, |a, b| Some(a * b))),
+                        BinaryOp::Div => Values::Number(zip_with(left, right, |a, b| {
+                            if b == 0.0 {
+                                None
+                            } else {
+                                Some(a / b)
+                            }
+                        })),
+                        BinaryOp::Lt => Values::Bool(zip_with(left, right, |a, b| Some(a < b))),
+                        BinaryOp::Le => Values::Bool(zip_with(left, right, |a, b| Some(a <= b))),
+                        BinaryOp::Gt => Values::Bool(zip_with(left, right, |a, b| Some(a > b))),
+                        BinaryOp::Ge => Values::Bool(zip_with(left, right, |a, b| Some(a >= b))),
+                        BinaryOp::Eq => Values::Bool(zip_with(left, right, |a, b| Some(a == b))),
+                        BinaryOp::Ne => Values::Bool(zip_with(left, right, |a, b| Some(a != b))),
+                        BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+                    }
+                }
+            }
+        }
+        Node::Cross { above, left, right } => {
+            let spread: Vec<Option<f64>> = zip_with(
+                evaluate(left, bars).numbers(),
+                evaluate(right, bars).numbers(),
+                |a, b| Some(if *above { a - b } else { b - a }),
+            );
+            let mut crossed = vec![None; spread.len().min(1)];
+            crossed.extend(spread.windows(2).map(|w| Some(w[0]? <= 0.0 && w[1]? > 0.0)));
+            Values::Bool(crossed)
+        }
+        Node::Prev(inner, n) => {
+            fn shift<T: Copy>(values: Vec<Option<T>>, n: usize) -> Vec<Option<T>> {
+                let len = values.len();
+                std::iter::repeat_n(None, n.min(len))
+                    .chain(values.into_iter().take(len.saturating_sub(n)))
+                    .collect()
+            }
+            match evaluate(inner, bars) {
+                Values::Number(values) => Values::Number(shift(values, *n)),
+                Values::Bool(values) => Values::Bool(shift(values, *n)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, 1 + i as u32, 0, 0, 0)
+                    .unwrap(),
+                open: *close - 1.0,
+                high: *close + 1.0,
+                low: *close - 2.0,
+                close: *close,
+                adjclose: *close,
+                volume: 100,
+            })
+            .collect()
+    }
+
+    fn numbers(source: &str, bars: &[Bar]) -> Vec<Option<f64>> {
+        match Expr::parse(source).unwrap().evaluate(bars) {
+            Values::Number(values) => values,
+            other => panic!("{:?} is not a number", other),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_numbers() {
+        let bars = bars(&[10.0, 12.0, 14.0]);
+        assert_eq!(numbers("sma(2)", &bars), vec![None, Some(11.0), Some(13.0)]);
+        assert_eq!(
+            numbers("(close - open) * 2 + -high / 11", &bars),
+            vec![Some(1.0), Some(2.0 - 13.0 / 11.0), Some(2.0 - 15.0 / 11.0)]
+        );
+        assert_eq!(
+            numbers("prev(close, 2)", &bars),
+            vec![None, None, Some(10.0)]
+        );
+        assert_eq!(
+            numbers("close / (close - 12)", &bars),
+            vec![Some(-5.0), None, Some(7.0)]
+        );
+        assert_eq!(numbers("abs(low - 13)", &bars)[0], Some(5.0));
+    }
+
+    #[test]
+    fn test_signals() {
+        let bars = bars(&[10.0, 9.0, 8.0, 10.0, 12.0, 11.0, 9.0]);
+        let signals = |source: &str| Expr::condition(source).unwrap().signals(&bars);
+
+        // sma_2: -, 9.5, 8.5, 9, 11, 11.5, 10
+        assert_eq!(
+            signals("close > sma(2)"),
+            vec![false, false, false, true, true, false, false]
+        );
+        assert_eq!(
+            signals("crosses_above(close, sma(2))"),
+            vec![false, false, false, true, false, false, false]
+        );
+        assert_eq!(
+            signals("crosses_below(close, sma(2))"),
+            vec![false, false, false, false, false, true, false]
+        );
+        assert_eq!(
+            signals("close >= 10 and not close == 12 or volume != 100"),
+            vec![true, false, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_unknown_values() {
+        let bars = bars(&[10.0, 9.0, 8.0]);
+        let values = |source: &str| match Expr::condition(source).unwrap().evaluate(&bars) {
+            Values::Bool(values) => values,
+            other => panic!("{:?} is not a condition", other),
+        };
+
+        // the known side decides `and` and `or` on the first bar
+        assert_eq!(values("sma(2) > 0 and false")[0], Some(false));
+        assert_eq!(values("sma(2) > 0 or true")[0], Some(true));
+        assert_eq!(values("sma(2) > 0 or false")[0], None);
+        assert_eq!(
+            values("prev(close > 9)"),
+            vec![None, Some(true), Some(false)]
+        );
+    }
+
+    #[test]
+    fn test_condition() {
+        assert_eq!(
+            Expr::condition("sma(20) > sma(50) and rsi(14) < 30")
+                .unwrap()
+                .to_string(),
+            "sma(20) > sma(50) and rsi(14) < 30"
+        );
+        let error = Expr::condition("  sma(20) * 2").unwrap_err();
+        assert_eq!(error.position, 2);
+        assert_eq!(
+            error.to_string(),
+            "expected a condition like 'close > sma(20)', not a number at column 3\n      sma(20) * 2\n      ^"
+        );
+        assert!("close".parse::<Expr>().is_err());
+    }
+}
@@ -0,0 +1,735 @@
+//!
+//! Turns the text of an expression into a type checked tree.
+//!
+//! ```text
+//! expression := or
+//! or         := and ("or" and)*
+//! and        := not ("and" not)*
+//! not        := "not" not | comparison
+//! comparison := sum (("<" | "<=" | ">" | ">=" | "==" | "!=") sum)?
+//! sum        := product (("+" | "-") product)*
+//! product    := unary (("*" | "/") unary)*
+//! unary      := "-" unary | primary
+//! primary    := number | name | name "(" (argument ("," argument)*)? ")" | "(" expression ")"
+//! ```
+//!
+
+use super::{BarField, BinaryOp, ExprError, Node, Type};
+use crate::indicators::Indicator;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    /// A number, possibly with a unit suffix like `30d`, kept as written
+    Number(String),
+    Name(String),
+    Operator(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(n) => format!("'{}'", n),
+            TokenKind::Name(n) => format!("'{}'", n),
+            TokenKind::Operator(op) => format!("'{}'", op),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::End => "the end".to_string(),
+        }
+    }
+}
+
+const OPERATORS: [&str; 10] = ["<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/"];
+
+/// The indicator functions: the name of the matching indicator and the column it returns.
+const INDICATORS: [(&str, &str, usize); 20] = [
+    ("sma", "sma", 0),
+    ("ema", "ema", 0),
+    ("wma", "wma", 0),
+    ("dema", "dema", 0),
+    ("tema", "tema", 0),
+    ("hma", "hma", 0),
+    ("rsi", "rsi", 0),
+    ("macd", "macd", 0),
+    ("macd_signal", "macd", 1),
+    ("macd_hist", "macd", 2),
+    ("bb_upper", "bb", 0),
+    ("bb_middle", "bb", 1),
+    ("bb_lower", "bb", 2),
+    ("tr", "tr", 0),
+    ("atr", "atr", 0),
+    ("stoch_k", "stoch", 0),
+    ("stoch_d", "stoch", 1),
+    ("vwap", "vwap", 0),
+    ("obv", "obv", 0),
+    ("adv", "adv", 0),
+];
+
+/// Functions that are not indicators.
+const FUNCTIONS: [&str; 4] = ["abs", "prev", "crosses_above", "crosses_below"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExprError> {
+    let error = |message: String, position| ExprError {
+        message,
+        position,
+        source: source.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        let mut word = |accept: fn(char) -> bool| {
+            let mut end = position;
+            while let Some(&(i, c)) = chars.peek() {
+                if !accept(c) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            source[position..end].to_string()
+        };
+        let kind = if c.is_whitespace() {
+            chars.next();
+            continue;
+        } else if c.is_ascii_digit() || c == '.' {
+            TokenKind::Number(word(|c| c.is_ascii_alphanumeric() || c == '.'))
+        } else if c.is_alphabetic() || c == '_' {
+            TokenKind::Name(word(|c| c.is_alphanumeric() || c == '_').to_lowercase())
+        } else {
+            chars.next();
+            match c {
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                ',' => TokenKind::Comma,
+                _ => {
+                    let rest = &source[position..];
+                    let operator = OPERATORS.iter().find(|op| rest.starts_with(*op));
+                    match operator {
+                        Some(op) => {
+                            for _ in 1..op.len() {
+                                chars.next();
+                            }
+                            TokenKind::Operator(op)
+                        }
+                        None if c == '=' => {
+                            return Err(error("expected '==' to compare".to_string(), position))
+                        }
+                        None => return Err(error(format!("unexpected '{}'", c), position)),
+                    }
+                }
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: source.len(),
+    });
+    Ok(tokens)
+}
+
+///
+/// A node with its type and where it starts, for error messages.
+///
+struct Typed {
+    node: Node,
+    kind: Type,
+    position: usize,
+}
+
+///
+/// An argument of a call. Numbers are kept as written, as indicator windows may carry units.
+///
+enum Argument {
+    Literal(String, usize),
+    Expression(Typed),
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+///
+/// Parses and type checks `source`.
+///
+/// # Returns
+/// The root of the tree and whether it is a number or a condition.
+///
+pub fn parse(source: &str) -> Result<(Node, Type), ExprError> {
+    let mut parser = Parser {
+        source,
+        tokens: tokenize(source)?,
+        next: 0,
+    };
+    if parser.peek().kind == TokenKind::End {
+        return Err(parser.error("the expression is empty", source.len()));
+    }
+    let expression = parser.expression()?;
+    let token = parser.peek().clone();
+    if token.kind != TokenKind::End {
+        return Err(parser.error(
+            &format!(
+                "expected an operator or the end, found {}",
+                token.kind.describe()
+            ),
+            token.position,
+        ));
+    }
+    Ok((expression.node, expression.kind))
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str, position: usize) -> ExprError {
+        ExprError {
+            message: message.to_string(),
+            position,
+            source: self.source.to_string(),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].clone();
+        if token.kind != TokenKind::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), ExprError> {
+        if self.eat(&kind) {
+            return Ok(());
+        }
+        let token = self.peek();
+        Err(self.error(
+            &format!(
+                "expected {}, found {}",
+                kind.describe(),
+                token.kind.describe()
+            ),
+            token.position,
+        ))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Name(name) if name == keyword)
+    }
+
+    fn require(&self, operand: &Typed, kind: Type, context: &str) -> Result<(), ExprError> {
+        if operand.kind == kind {
+            return Ok(());
+        }
+        Err(self.error(
+            &format!("{} expects {}, not {}", context, kind, operand.kind),
+            operand.position,
+        ))
+    }
+
+    fn expression(&mut self) -> Result<Typed, ExprError> {
+        self.or()
+    }
+
+    fn logical(
+        &mut self,
+        keyword: &'static str,
+        op: BinaryOp,
+        operand: fn(&mut Self) -> Result<Typed, ExprError>,
+    ) -> Result<Typed, ExprError> {
+        let mut left = operand(self)?;
+        while self.is_keyword(keyword) {
+            self.advance();
+            let right = operand(self)?;
+            let context = format!("'{}'", keyword);
+            self.require(&left, Type::Bool, &context)?;
+            self.require(&right, Type::Bool, &context)?;
+            left = Typed {
+                node: Node::Binary(op, Box::new(left.node), Box::new(right.node)),
+                kind: Type::Bool,
+                position: left.position,
+            };
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Typed, ExprError> {
+        self.logical("or", BinaryOp::Or, Self::and)
+    }
+
+    fn and(&mut self) -> Result<Typed, ExprError> {
+        self.logical("and", BinaryOp::And, Self::not)
+    }
+
+    fn not(&mut self) -> Result<Typed, ExprError> {
+        if !self.is_keyword("not") {
+            return self.comparison();
+        }
+        let position = self.advance().position;
+        let operand = self.not()?;
+        self.require(&operand, Type::Bool, "'not'")?;
+        Ok(Typed {
+            node: Node::Not(Box::new(operand.node)),
+            kind: Type::Bool,
+            position,
+        })
+    }
+
+    fn comparison_operator(&self) -> Option<BinaryOp> {
+        match self.peek().kind {
+            TokenKind::Operator("<") => Some(BinaryOp::Lt),
+            TokenKind::Operator("<=") => Some(BinaryOp::Le),
+            TokenKind::Operator(">") => Some(BinaryOp::Gt),
+            TokenKind::Operator(">=") => Some(BinaryOp::Ge),
+            TokenKind::Operator("==") => Some(BinaryOp::Eq),
+            TokenKind::Operator("!=") => Some(BinaryOp::Ne),
+            _ => None,
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Typed, ExprError> {
+        let left = self.sum()?;
+        let op = match self.comparison_operator() {
+            Some(op) => op,
+            None => return Ok(left),
+        };
+        let token = self.advance();
+        let right = self.sum()?;
+        let context = format!(
+            "'{}'",
+            self.source[token.position..]
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+        );
+        self.require(&left, Type::Number, &context)?;
+        self.require(&right, Type::Number, &context)?;
+        if self.comparison_operator().is_some() {
+            return Err(self.error(
+                "comparisons cannot be chained, combine them with 'and'",
+                self.peek().position,
+            ));
+        }
+        Ok(Typed {
+            node: Node::Binary(op, Box::new(left.node), Box::new(right.node)),
+            kind: Type::Bool,
+            position: left.position,
+        })
+    }
+
+    fn arithmetic(
+        &mut self,
+        operators: [(&'static str, BinaryOp); 2],
+        operand: fn(&mut Self) -> Result<Typed, ExprError>,
+    ) -> Result<Typed, ExprError> {
+        let mut left = operand(self)?;
+        loop {
+            let op = operators
+                .iter()
+                .find(|(symbol, _)| self.peek().kind == TokenKind::Operator(symbol));
+            let (symbol, op) = match op {
+                Some(op) => *op,
+                None => return Ok(left),
+            };
+            self.advance();
+            let right = operand(self)?;
+            let context = format!("'{}'", symbol);
+            self.require(&left, Type::Number, &context)?;
+            self.require(&right, Type::Number, &context)?;
+            left = Typed {
+                node: Node::Binary(op, Box::new(left.node), Box::new(right.node)),
+                kind: Type::Number,
+                position: left.position,
+            };
+        }
+    }
+
+    fn sum(&mut self) -> Result<Typed, ExprError> {
+        self.arithmetic([("+", BinaryOp::Add), ("-", BinaryOp::Sub)], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Typed, ExprError> {
+        self.arithmetic([("*", BinaryOp::Mul), ("/", BinaryOp::Div)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Typed, ExprError> {
+        if self.peek().kind != TokenKind::Operator("-") {
+            return self.primary();
+        }
+        let position = self.advance().position;
+        let operand = self.unary()?;
+        self.require(&operand, Type::Number, "'-'")?;
+        Ok(Typed {
+            node: Node::Neg(Box::new(operand.node)),
+            kind: Type::Number,
+            position,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Typed, ExprError> {
+        let token = self.advance();
+        let typed = |node, kind| Typed {
+            node,
+            kind,
+            position: token.position,
+        };
+        match &token.kind {
+            TokenKind::Number(text) => Ok(typed(
+                Node::Number(self.number(text, token.position)?),
+                Type::Number,
+            )),
+            TokenKind::LParen => {
+                let inner = self.expression()?;
+                self.expect(TokenKind::RParen)?;
+                Ok(Typed {
+                    position: token.position,
+                    ..inner
+                })
+            }
+            TokenKind::Name(name) if self.peek().kind == TokenKind::LParen => {
+                self.call(name, token.position)
+            }
+            TokenKind::Name(name) => {
+                let field = |field| Ok(typed(Node::Field(field), Type::Number));
+                match name.as_str() {
+                    "open" => field(BarField::Open),
+                    "high" => field(BarField::High),
+                    "low" => field(BarField::Low),
+                    "close" | "price" => field(BarField::Close),
+                    "volume" => field(BarField::Volume),
+                    "true" => Ok(typed(Node::Bool(true), Type::Bool)),
+                    "false" => Ok(typed(Node::Bool(false), Type::Bool)),
+                    name if is_function(name) => Err(self.error(
+                        &format!("'{}' is a function, call it like {}(...)", name, name),
+                        token.position,
+                    )),
+                    name => Err(self.error(
+                        &format!("unknown name '{}', expected open, high, low, close, volume or a function like sma(20)", name),
+                        token.position,
+                    )),
+                }
+            }
+            kind => Err(self.error(
+                &format!("expected a value, found {}", kind.describe()),
+                token.position,
+            )),
+        }
+    }
+
+    ///
+    /// Parses the arguments of a call, whose name has already been consumed.
+    ///
+    fn arguments(&mut self) -> Result<Vec<Argument>, ExprError> {
+        self.expect(TokenKind::LParen)?;
+        let mut arguments = Vec::new();
+        if self.eat(&TokenKind::RParen) {
+            return Ok(arguments);
+        }
+        loop {
+            let token = self.peek().clone();
+            let literal = matches!(
+                self.tokens[self.next + 1].kind,
+                TokenKind::Comma | TokenKind::RParen
+            );
+            arguments.push(match token.kind {
+                TokenKind::Number(text) if literal => {
+                    self.advance();
+                    Argument::Literal(text, token.position)
+                }
+                _ => Argument::Expression(self.expression()?),
+            });
+            if self.eat(&TokenKind::RParen) {
+                return Ok(arguments);
+            }
+            let token = self.peek().clone();
+            if !self.eat(&TokenKind::Comma) {
+                return Err(self.error(
+                    &format!("expected ',' or ')', found {}", token.kind.describe()),
+                    token.position,
+                ));
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, position: usize) -> Result<Typed, ExprError> {
+        let arguments = self.arguments()?;
+        if is_indicator(name) {
+            return self.indicator(name, position, arguments);
+        }
+        let count = arguments.len();
+        let count_error = |expected: &str| {
+            self.error(
+                &format!("'{}' expects {}, got {}", name, expected, count),
+                position,
+            )
+        };
+        let typed = |node, kind| Typed {
+            node,
+            kind,
+            position,
+        };
+        let mut operands = Vec::new();
+        for argument in arguments {
+            operands.push(match argument {
+                Argument::Literal(text, position) => Typed {
+                    node: Node::Number(self.number(&text, position)?),
+                    kind: Type::Number,
+                    position,
+                },
+                Argument::Expression(typed) => typed,
+            });
+        }
+        let mut operands = operands.into_iter();
+
+        match name {
+            "abs" => {
+                let operand = match (operands.next(), operands.next()) {
+                    (Some(operand), None) => operand,
+                    _ => return Err(count_error("one argument")),
+                };
+                self.require(&operand, Type::Number, "'abs'")?;
+                Ok(typed(Node::Abs(Box::new(operand.node)), Type::Number))
+            }
+            "crosses_above" | "crosses_below" => {
+                let (left, right) = match (operands.next(), operands.next(), operands.next()) {
+                    (Some(left), Some(right), None) => (left, right),
+                    _ => return Err(count_error("two arguments")),
+                };
+                let context = format!("'{}'", name);
+                self.require(&left, Type::Number, &context)?;
+                self.require(&right, Type::Number, &context)?;
+                Ok(typed(
+                    Node::Cross {
+                        above: name == "crosses_above",
+                        left: Box::new(left.node),
+                        right: Box::new(right.node),
+                    },
+                    Type::Bool,
+                ))
+            }
+            "prev" => {
+                let (operand, bars) = match (operands.next(), operands.next(), operands.next()) {
+                    (Some(operand), None, None) => (operand, 1),
+                    (Some(operand), Some(bars), None) => match bars.node {
+                        Node::Number(n) if n >= 1.0 && n.fract() == 0.0 => (operand, n as usize),
+                        _ => {
+                            return Err(self.error(
+                                "'prev' expects a whole number of bars of at least 1",
+                                bars.position,
+                            ))
+                        }
+                    },
+                    _ => return Err(count_error("one or two arguments")),
+                };
+                Ok(Typed {
+                    node: Node::Prev(Box::new(operand.node), bars),
+                    kind: operand.kind,
+                    position,
+                })
+            }
+            _ => Err(self.error(
+                &format!(
+                    "unknown function '{}', expected one of {}",
+                    name,
+                    INDICATORS
+                        .iter()
+                        .map(|(function, _, _)| *function)
+                        .chain(FUNCTIONS.iter().copied())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                position,
+            )),
+        }
+    }
+
+    ///
+    /// Builds an indicator call from literal parameters, using the parser of `--indicators`.
+    ///
+    fn indicator(
+        &self,
+        name: &str,
+        position: usize,
+        arguments: Vec<Argument>,
+    ) -> Result<Typed, ExprError> {
+        let (indicator, column) = INDICATORS
+            .iter()
+            .find(|(function, _, _)| *function == name)
+            .map(|(_, indicator, column)| (*indicator, *column))
+            .expect("a known indicator");
+        let mut spec = vec![indicator.to_string()];
+        for argument in arguments {
+            match argument {
+                Argument::Literal(text, _) => spec.push(text),
+                Argument::Expression(typed) => {
+                    return Err(self.error(
+                        &format!("the parameters of '{}' have to be numbers", name),
+                        typed.position,
+                    ))
+                }
+            }
+        }
+        let indicator: Indicator = spec.join(":").parse().map_err(|e: String| {
+            self.error(
+                &format!("invalid parameters for '{}': {}", name, e),
+                position,
+            )
+        })?;
+        Ok(Typed {
+            node: Node::Indicator { indicator, column },
+            kind: Type::Number,
+            position,
+        })
+    }
+
+    fn number(&self, text: &str, position: usize) -> Result<f64, ExprError> {
+        text.parse().map_err(|_| {
+            self.error(
+                &format!(
+                    "invalid number '{}', durations like 30d are only allowed as indicator windows",
+                    text
+                ),
+                position,
+            )
+        })
+    }
+}
+
+fn is_indicator(name: &str) -> bool {
+    INDICATORS.iter().any(|(function, _, _)| *function == name)
+}
+
+fn is_function(name: &str) -> bool {
+    is_indicator(name) || FUNCTIONS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::Window;
+
+    fn error(source: &str) -> (String, usize) {
+        let e = parse(source).unwrap_err();
+        (e.message, e.position)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let kinds: Vec<TokenKind> = tokenize("sma(30d)>=1.5")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Name("sma".to_string()),
+                TokenKind::LParen,
+                TokenKind::Number("30d".to_string()),
+                TokenKind::RParen,
+                TokenKind::Operator(">="),
+                TokenKind::Number("1.5".to_string()),
+                TokenKind::End,
+            ]
+        );
+        assert!(tokenize("close = 3").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let (node, kind) = parse("sma(30d) > macd_signal(12, 26, 9) * -2 and not false").unwrap();
+        assert_eq!(kind, Type::Bool);
+        let comparison = Node::Binary(
+            BinaryOp::Gt,
+            Box::new(Node::Indicator {
+                indicator: Indicator::Sma(Window::Duration(chrono::Duration::days(30))),
+                column: 0,
+            }),
+            Box::new(Node::Binary(
+                BinaryOp::Mul,
+                Box::new(Node::Indicator {
+                    indicator: Indicator::Macd {
+                        fast: 12,
+                        slow: 26,
+                        signal: 9,
+                    },
+                    column: 1,
+                }),
+                Box::new(Node::Neg(Box::new(Node::Number(2.0)))),
+            )),
+        );
+        assert_eq!(
+            node,
+            Node::Binary(
+                BinaryOp::And,
+                Box::new(comparison),
+                Box::new(Node::Not(Box::new(Node::Bool(false))))
+            )
+        );
+
+        // `and` binds tighter than `or`
+        let (node, _) = parse("true or false and false").unwrap();
+        assert!(matches!(node, Node::Binary(BinaryOp::Or, _, _)));
+        assert_eq!(parse("prev(rsi(), 3)").unwrap().1, Type::Number);
+        assert_eq!(parse("(close)").unwrap().0, Node::Field(BarField::Close));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            error("close > sma(20"),
+            ("expected ',' or ')', found the end".to_string(), 14)
+        );
+        assert_eq!(
+            error("sma(20) and close > 1"),
+            ("'and' expects a condition, not a number".to_string(), 0)
+        );
+        assert_eq!(
+            error("1 < close < 3"),
+            (
+                "comparisons cannot be chained, combine them with 'and'".to_string(),
+                10
+            )
+        );
+        assert_eq!(error("rsi(14) < 30 or smaa(3) > 1").1, 16);
+        assert!(error("smaa(3)").0.starts_with("unknown function 'smaa'"));
+        assert!(error("sma(1)")
+            .0
+            .starts_with("invalid parameters for 'sma'"));
+        assert_eq!(error("sma(close)").1, 4);
+        assert_eq!(error("clse > 1").1, 0);
+        assert_eq!(
+            error("vwap > 1").0,
+            "'vwap' is a function, call it like vwap(...)"
+        );
+        assert_eq!(error("close > 30d").1, 8);
+        assert_eq!(error("prev(close, 0)").1, 12);
+        assert_eq!(
+            error("close 3").0,
+            "expected an operator or the end, found '3'"
+        );
+        assert_eq!(error("   ").0, "the expression is empty");
+    }
+}
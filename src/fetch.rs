@@ -0,0 +1,229 @@
+//!
+//! Concurrent fetching of many symbols.
+//!
+
+use crate::error::Error;
+use crate::model::{Bar, BarInterval, Event};
+use crate::provider::{ProviderResult, QuoteProvider};
+use chrono::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+///
+/// Limits for `fetch_all` and `fetch_all_events`, and the interval of the fetched bars.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct FetchOptions {
+    /// The maximum number of requests in flight at once
+    pub concurrency: usize,
+    /// How long a single request may take before it is reported as failed
+    pub timeout: Option<Duration>,
+    /// The interval to fetch bars at, or `None` for the provider's own bars
+    pub interval: Option<BarInterval>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            concurrency: 8,
+            timeout: Some(Duration::from_secs(30)),
+            interval: None,
+        }
+    }
+}
+
+///
+/// Splits a comma separated list of symbols, trimming them and dropping empty and repeated
+/// ones, so that every symbol is fetched and reported once.
+///
+pub fn parse_symbols(s: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in s.split(',').map(str::trim) {
+        if !symbol.is_empty() && !symbols.iter().any(|s| s == symbol) {
+            symbols.push(symbol.to_string());
+        }
+    }
+    symbols
+}
+
+///
+/// Fetches the bars of all `symbols` concurrently.
+///
+/// Providers are blocking, so every request runs on tokio's blocking thread pool while holding
+/// a permit; the permit is only returned once the request has actually finished, so timed out
+/// requests still count against `concurrency`.
+///
+/// # Returns
+/// One result per symbol, in the order of `symbols`.
+///
+pub async fn fetch_all(
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    options: FetchOptions,
+) -> Vec<(String, ProviderResult<Vec<Bar>>)> {
+    fetch_each(
+        provider,
+        symbols,
+        options,
+        move |provider, symbol| match options.interval {
+            Some(interval) => provider.fetch_bars(symbol, from, to, interval),
+            None => provider.fetch_history(symbol, from, to),
+        },
+    )
+    .await
+}
+
+///
+/// Fetches the corporate actions of all `symbols` concurrently, with the same limits as
+/// `fetch_all`.
+///
+pub async fn fetch_all_events(
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    options: FetchOptions,
+) -> Vec<(String, ProviderResult<Vec<Event>>)> {
+    fetch_each(provider, symbols, options, move |provider, symbol| {
+        provider.fetch_events(symbol, from, to)
+    })
+    .await
+}
+
+async fn fetch_each<T, F>(
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    options: FetchOptions,
+    request: F,
+) -> Vec<(String, ProviderResult<T>)>
+where
+    T: Send + 'static,
+    F: Fn(&dyn QuoteProvider, &str) -> ProviderResult<T> + Copy + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(options.concurrency.max(1)));
+
+    let tasks: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            let (provider, permits, symbol) = (provider.clone(), permits.clone(), symbol.clone());
+            tokio::spawn(async move {
+                let permit = permits
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| Error::Provider(e.to_string()))?;
+                let request = {
+                    let symbol = symbol.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let _permit = permit;
+                        request(provider.as_ref(), &symbol)
+                    })
+                };
+
+                match options.timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, request).await {
+                        Ok(result) => result?,
+                        Err(_) => Err(Error::Network(format!(
+                            "request timed out after {:?}",
+                            timeout
+                        ))),
+                    },
+                    None => request.await?,
+                }
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (symbol, task) in symbols.iter().zip(tasks) {
+        let result = match task.await {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        };
+        results.push((symbol.clone(), result));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct SlowProvider {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl QuoteProvider for SlowProvider {
+        fn fetch_history(
+            &self,
+            symbol: &str,
+            from: DateTime<Utc>,
+            _to: DateTime<Utc>,
+        ) -> ProviderResult<Vec<Bar>> {
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+            // later symbols finish first, so the output order has to be restored
+            let delay = if symbol == "SLOW" {
+                500
+            } else {
+                60 - symbol.len() as u64 * 10
+            };
+            std::thread::sleep(Duration::from_millis(delay));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(vec![Bar {
+                timestamp: from,
+                open: 1.0,
+                high: 1.0,
+                low: 1.0,
+                close: symbol.len() as f64,
+                adjclose: symbol.len() as f64,
+                volume: 0,
+            }])
+        }
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        assert_eq!(parse_symbols("MSFT,AAPL"), vec!["MSFT", "AAPL"]);
+        assert_eq!(
+            parse_symbols("MSFT, AAPL, AAPL,,MSFT "),
+            vec!["MSFT", "AAPL"]
+        );
+        assert!(parse_symbols(" ").is_empty());
+    }
+
+    #[test]
+    fn test_fetch_all() {
+        let provider = Arc::new(SlowProvider {
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        });
+        let symbols: Vec<String> = ["A", "BB", "CCC", "DDDD", "SLOW"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let now = Utc::now();
+        let options = FetchOptions {
+            concurrency: 2,
+            timeout: Some(Duration::from_millis(200)),
+            ..FetchOptions::default()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let results = runtime.block_on(fetch_all(provider.clone(), &symbols, now, now, options));
+
+        let returned: Vec<&str> = results.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(returned, vec!["A", "BB", "CCC", "DDDD", "SLOW"]);
+        for (symbol, result) in &results[..4] {
+            assert_eq!(result.as_ref().unwrap()[0].close, symbol.len() as f64);
+        }
+        assert!(results[4].1.is_err());
+        assert!(provider.max_in_flight.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,628 @@
+//!
+//! Indicators computed over a series of prices.
+//!
+
+use crate::model::Bar;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod bands;
+pub mod momentum;
+pub mod moving_average;
+pub mod range;
+pub mod volume;
+
+use bands::bollinger;
+use momentum::{macd, n_window_rsi};
+use moving_average::{n_window_dema, n_window_ema, n_window_hma, n_window_tema, n_window_wma};
+use range::{n_window_atr, stochastic, true_range};
+use volume::{obv, vwap};
+
+///
+/// The length of a moving window: either a number of bars or a calendar duration.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Bars(usize),
+    Duration(Duration),
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    ///
+    /// Parses `20` as 20 bars and `30d`, `4w` or `12h` as calendar durations.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value: i64 = value
+            .parse()
+            .map_err(|_| format!("invalid window '{}'", s))?;
+        if value <= 0 {
+            return Err(format!("the window '{}' has to be positive", s));
+        }
+
+        match unit {
+            "" if value < 2 => Err(format!(
+                "invalid window '{}', expected a number of bars above 1",
+                s
+            )),
+            "" => Ok(Window::Bars(value as usize)),
+            "h" => Ok(Window::Duration(Duration::hours(value))),
+            "d" => Ok(Window::Duration(Duration::days(value))),
+            "w" => Ok(Window::Duration(Duration::weeks(value))),
+            _ => Err(format!(
+                "invalid window unit '{}', expected h, d or w",
+                unit
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Window::Bars(n) => write!(f, "{}", n),
+            Window::Duration(d)
+                if d.num_weeks() * 7 == d.num_days() && d.num_days() * 24 == d.num_hours() =>
+            {
+                write!(f, "{}w", d.num_weeks())
+            }
+            Window::Duration(d) if d.num_days() * 24 == d.num_hours() => {
+                write!(f, "{}d", d.num_days())
+            }
+            Window::Duration(d) => write!(f, "{}h", d.num_hours()),
+        }
+    }
+}
+
+///
+/// An indicator that can be computed for every bar of a series, e.g. to become a summary column.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indicator {
+    Sma(Window),
+    Ema(usize),
+    Wma(usize),
+    Dema(usize),
+    Tema(usize),
+    Hma(usize),
+    Rsi(usize),
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        sigma: f64,
+    },
+    TrueRange,
+    Atr(usize),
+    Stochastic {
+        k: usize,
+        d: usize,
+    },
+    Vwap,
+    Obv,
+    /// The average volume over a window
+    Adv(Window),
+}
+
+///
+/// Pads `values`, which belong to the last bars of a series, with `None` to `len` elements.
+///
+fn align(values: Option<Vec<f64>>, len: usize) -> Vec<Option<f64>> {
+    let values = values.unwrap_or_default();
+    let padding = len.saturating_sub(values.len());
+    std::iter::repeat_n(None, padding)
+        .chain(values.into_iter().map(Some))
+        .take(len)
+        .collect()
+}
+
+impl Indicator {
+    ///
+    /// Computes the indicator over the adjusted closes of `bars`. Indicators that need the whole
+    /// bar use bars adjusted like the close, see `Bar::adjusted`.
+    ///
+    /// # Returns
+    /// The named columns of the indicator, each with one value per bar. Values are `None` until
+    /// there are enough bars.
+    ///
+    pub fn compute(&self, bars: &[Bar]) -> Vec<(String, Vec<Option<f64>>)> {
+        let closes: Vec<f64> = bars.iter().map(|b| b.adjclose).collect();
+        let timestamps: Vec<DateTime<Utc>> = bars.iter().map(|b| b.timestamp).collect();
+        let adjusted: Vec<Bar> = bars.iter().map(Bar::adjusted).collect();
+        let len = bars.len();
+        let values = match self {
+            Indicator::Sma(window) => window_sma(*window, &timestamps, &closes),
+            Indicator::Ema(n) => n_window_ema(*n, &closes),
+            Indicator::Wma(n) => n_window_wma(*n, &closes),
+            Indicator::Dema(n) => n_window_dema(*n, &closes),
+            Indicator::Tema(n) => n_window_tema(*n, &closes),
+            Indicator::Hma(n) => n_window_hma(*n, &closes),
+            Indicator::Rsi(n) => n_window_rsi(*n, &closes),
+            Indicator::Macd { fast, slow, signal } => {
+                let suffix = format!("{}_{}_{}", fast, slow, signal);
+                let lines = macd(*fast, *slow, *signal, &closes);
+                let (line, signal, histogram) = match lines {
+                    Some(m) => (Some(m.macd), Some(m.signal), Some(m.histogram)),
+                    None => (None, None, None),
+                };
+                return vec![
+                    (format!("macd_{}", suffix), align(line, len)),
+                    (format!("macd_signal_{}", suffix), align(signal, len)),
+                    (format!("macd_hist_{}", suffix), align(histogram, len)),
+                ];
+            }
+            Indicator::Bollinger { period, sigma } => {
+                let suffix = format!("{}_{}", period, sigma);
+                let bands = bollinger(*period, *sigma, &closes);
+                let (upper, middle, lower) = match bands {
+                    Some(b) => (Some(b.upper), Some(b.middle), Some(b.lower)),
+                    None => (None, None, None),
+                };
+                return vec![
+                    (format!("bb_upper_{}", suffix), align(upper, len)),
+                    (format!("bb_middle_{}", suffix), align(middle, len)),
+                    (format!("bb_lower_{}", suffix), align(lower, len)),
+                ];
+            }
+            Indicator::TrueRange => true_range(&adjusted),
+            Indicator::Atr(n) => n_window_atr(*n, &adjusted),
+            Indicator::Stochastic { k, d } => {
+                let (percent_k, percent_d) = match stochastic(*k, *d, &adjusted) {
+                    Some(s) => (Some(s.k), Some(s.d)),
+                    None => (None, None),
+                };
+                return vec![
+                    (format!("stoch_k_{}_{}", k, d), align(percent_k, len)),
+                    (format!("stoch_d_{}_{}", k, d), align(percent_d, len)),
+                ];
+            }
+            Indicator::Vwap => vwap(&adjusted),
+            Indicator::Obv => obv(&adjusted),
+            Indicator::Adv(window) => {
+                let volumes: Vec<f64> = bars.iter().map(|b| b.volume as f64).collect();
+                window_sma(*window, &timestamps, &volumes)
+            }
+        };
+        vec![(self.to_string(), align(values, len))]
+    }
+
+    ///
+    /// The history the indicator needs before its first value, with bars `bar` apart.
+    ///
+    pub fn lookback(&self, bar: Duration) -> Duration {
+        let bars = match *self {
+            Indicator::Sma(Window::Duration(d)) | Indicator::Adv(Window::Duration(d)) => return d,
+            Indicator::Sma(Window::Bars(n)) | Indicator::Adv(Window::Bars(n)) => n,
+            Indicator::Ema(n) | Indicator::Wma(n) | Indicator::Bollinger { period: n, .. } => n,
+            Indicator::Rsi(n) | Indicator::Atr(n) => n + 1,
+            Indicator::Dema(n) => 2 * n,
+            Indicator::Tema(n) => 3 * n,
+            Indicator::Hma(n) => n + (n as f64).sqrt() as usize,
+            Indicator::Macd { slow, signal, .. } => slow + signal,
+            Indicator::Stochastic { k, d } => k + d,
+            Indicator::TrueRange => 2,
+            Indicator::Vwap | Indicator::Obv => 1,
+        };
+        bar * bars as i32
+    }
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indicator::Sma(window) => write!(f, "sma_{}", window),
+            Indicator::Ema(n) => write!(f, "ema_{}", n),
+            Indicator::Wma(n) => write!(f, "wma_{}", n),
+            Indicator::Dema(n) => write!(f, "dema_{}", n),
+            Indicator::Tema(n) => write!(f, "tema_{}", n),
+            Indicator::Hma(n) => write!(f, "hma_{}", n),
+            Indicator::Rsi(n) => write!(f, "rsi_{}", n),
+            Indicator::Macd { fast, slow, signal } => {
+                write!(f, "macd_{}_{}_{}", fast, slow, signal)
+            }
+            Indicator::Bollinger { period, sigma } => write!(f, "bb_{}_{}", period, sigma),
+            Indicator::TrueRange => write!(f, "tr"),
+            Indicator::Atr(n) => write!(f, "atr_{}", n),
+            Indicator::Stochastic { k, d } => write!(f, "stoch_{}_{}", k, d),
+            Indicator::Vwap => write!(f, "vwap"),
+            Indicator::Obv => write!(f, "obv"),
+            Indicator::Adv(window) => write!(f, "adv_{}", window),
+        }
+    }
+}
+
+impl FromStr for Indicator {
+    type Err = String;
+
+    ///
+    /// Parses `<name>:<parameters>`, e.g. `sma:30d`, `ema:20`, `rsi:14`, `macd:12:26:9` or `bb:20:2`.
+    /// The parameters of `rsi`, `macd` and `bb` default to the values in these examples.
+    ///
+    /// Bar based indicators are `tr`, `atr:14`, `stoch:14:3`, `vwap`, `obv` and `adv:20`, the
+    /// latter taking a window like `sma`. Their parameters default to these values as well.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let params: Vec<&str> = parts.collect();
+        let bars = || -> Result<usize, String> {
+            match params.as_slice() {
+                [n] => n.parse().ok().filter(|n| *n > 1).ok_or_else(|| {
+                    format!(
+                        "invalid window '{}' for '{}', expected a number of bars above 1",
+                        n, name
+                    )
+                }),
+                _ => Err(format!(
+                    "'{}' expects a single window, e.g. '{}:20'",
+                    name, name
+                )),
+            }
+        };
+
+        match name.as_str() {
+            "sma" => match params.as_slice() {
+                [window] => Ok(Indicator::Sma(window.parse()?)),
+                _ => Err("'sma' expects a single window, e.g. 'sma:20' or 'sma:30d'".to_string()),
+            },
+            "ema" => Ok(Indicator::Ema(bars()?)),
+            "wma" => Ok(Indicator::Wma(bars()?)),
+            "dema" => Ok(Indicator::Dema(bars()?)),
+            "tema" => Ok(Indicator::Tema(bars()?)),
+            "hma" => Ok(Indicator::Hma(bars()?)),
+            "rsi" if params.is_empty() => Ok(Indicator::Rsi(14)),
+            "rsi" => Ok(Indicator::Rsi(bars()?)),
+            "macd" => {
+                let periods = params
+                    .iter()
+                    .map(|p| p.parse::<usize>().ok().filter(|p| *p > 1))
+                    .collect::<Option<Vec<usize>>>();
+                match periods.as_deref() {
+                    Some([]) => Ok(Indicator::Macd {
+                        fast: 12,
+                        slow: 26,
+                        signal: 9,
+                    }),
+                    Some([fast, slow, signal]) if fast < slow => Ok(Indicator::Macd {
+                        fast: *fast,
+                        slow: *slow,
+                        signal: *signal,
+                    }),
+                    _ => Err("'macd' expects <fast>:<slow>:<signal> bars with fast < slow, e.g. 'macd:12:26:9'".to_string()),
+                }
+            }
+            "tr" | "vwap" | "obv" if !params.is_empty() => {
+                Err(format!("'{}' takes no parameters", name))
+            }
+            "tr" => Ok(Indicator::TrueRange),
+            "vwap" => Ok(Indicator::Vwap),
+            "obv" => Ok(Indicator::Obv),
+            "atr" if params.is_empty() => Ok(Indicator::Atr(14)),
+            "atr" => Ok(Indicator::Atr(bars()?)),
+            "stoch" => {
+                let periods = params
+                    .iter()
+                    .map(|p| p.parse::<usize>().ok().filter(|p| *p > 0))
+                    .collect::<Option<Vec<usize>>>();
+                match periods.as_deref() {
+                    Some([]) => Ok(Indicator::Stochastic { k: 14, d: 3 }),
+                    Some([k, d]) if *d > 1 => Ok(Indicator::Stochastic { k: *k, d: *d }),
+                    _ => Err("'stoch' expects <k>:<d> bars, e.g. 'stoch:14:3'".to_string()),
+                }
+            }
+            "adv" => match params.as_slice() {
+                [] => Ok(Indicator::Adv(Window::Bars(20))),
+                [window] => Ok(Indicator::Adv(window.parse()?)),
+                _ => Err("'adv' expects a single window, e.g. 'adv:20' or 'adv:30d'".to_string()),
+            },
+            "bb" => {
+                let period = params
+                    .first()
+                    .map_or(Some(20), |p| p.parse().ok().filter(|p| *p > 1));
+                let sigma = params
+                    .get(1)
+                    .map_or(Some(2.0), |s| s.parse().ok().filter(|s: &f64| *s > 0.0));
+                match (period, sigma) {
+                    (Some(period), Some(sigma)) if params.len() <= 2 => {
+                        Ok(Indicator::Bollinger { period, sigma })
+                    }
+                    _ => Err("'bb' expects <period>:<sigma>, e.g. 'bb:20:2'".to_string()),
+                }
+            }
+            _ => Err(format!("unknown indicator '{}'", name)),
+        }
+    }
+}
+
+///
+/// Calculates the absolute / relative price change between the beginning and ending of f64 series.
+///
+/// # Returns
+/// A tuple `(absolute, relative)` diff.
+///
+pub fn price_diff(a: &[f64]) -> Option<(f64, f64)> {
+    if !a.is_empty() {
+        let (first, last) = (a.first().unwrap(), a.last().unwrap());
+        let diff = last - first;
+
+        let first = if *first == 0.0 { 1.0 } else { *first };
+        let rel_diff = diff / first;
+
+        Some((diff, rel_diff))
+    } else {
+        None
+    }
+}
+
+///
+/// Calculate a simple moving average over the entire series.
+///
+pub fn n_window_sma(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if !series.is_empty() && n > 1 {
+        Some(
+            series
+                .windows(n)
+                .map(|w| w.iter().sum::<f64>() / w.len() as f64)
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+///
+/// Calculate a simple moving average over a calendar duration.
+///
+/// Each average covers the values with a timestamp in `(t - window, t]`. Like `n_window_sma`,
+/// only complete windows are returned, i.e. those starting no earlier than the first timestamp.
+///
+pub fn duration_sma(
+    window: Duration,
+    timestamps: &[DateTime<Utc>],
+    series: &[f64],
+) -> Option<Vec<f64>> {
+    if series.is_empty() || timestamps.len() != series.len() || window <= Duration::zero() {
+        return None;
+    }
+
+    let first = timestamps[0];
+    let mut start = 0;
+    let mut sum = 0.0;
+    let mut averages = Vec::new();
+    for (i, (t, value)) in timestamps.iter().zip(series).enumerate() {
+        sum += value;
+        while timestamps[start] <= *t - window {
+            sum -= series[start];
+            start += 1;
+        }
+        if *t - window >= first {
+            averages.push(sum / (i + 1 - start) as f64);
+        }
+    }
+    Some(averages)
+}
+
+///
+/// Calculate the simple moving average for `window`, see `n_window_sma` and `duration_sma`.
+///
+pub fn window_sma(
+    window: Window,
+    timestamps: &[DateTime<Utc>],
+    series: &[f64],
+) -> Option<Vec<f64>> {
+    match window {
+        Window::Bars(n) => n_window_sma(n, series),
+        Window::Duration(d) => duration_sma(d, timestamps, series),
+    }
+}
+
+///
+/// Find the max value in a series of f64
+///
+pub fn max(series: &[f64]) -> Option<f64> {
+    if series.is_empty() {
+        None
+    } else {
+        Some(series.iter().fold(f64::MIN, |acc, q| acc.max(*q)))
+    }
+}
+
+///
+/// Find the min value in a series of f64
+///
+pub fn min(series: &[f64]) -> Option<f64> {
+    if series.is_empty() {
+        None
+    } else {
+        Some(series.iter().fold(f64::MAX, |acc, q| acc.min(*q)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_diff() {
+        assert_eq!(price_diff(&[]), None);
+        assert_eq!(price_diff(&[1.0]), Some((0.0, 0.0)));
+        assert_eq!(price_diff(&[1.0, 0.0]), Some((-1.0, -1.0)));
+        assert_eq!(price_diff(&[2.0, 3.0, 12.0, 9.0, 10.0]), Some((8.0, 4.0)));
+        assert_eq!(price_diff(&[0.0, 3.0, 12.0, 9.0, 10.0]), Some((10.0, 10.0)));
+    }
+
+    #[test]
+    fn test_n_window_sma() {
+        let series = vec![2.0, 4.5, 5.3, 6.5, 4.7];
+
+        assert_eq!(
+            n_window_sma(3, &series),
+            Some(vec![3.9333333333333336, 5.433333333333334, 5.5])
+        );
+        assert_eq!(n_window_sma(5, &series), Some(vec![4.6]));
+        assert_eq!(n_window_sma(10, &series), Some(vec![]));
+        assert_eq!(n_window_sma(1, &series), None);
+        assert_eq!(n_window_sma(3, &[]), None);
+    }
+
+    #[test]
+    fn test_duration_sma() {
+        let timestamps: Vec<DateTime<Utc>> = [1, 2, 3, 6, 7]
+            .iter()
+            .map(|d| Utc.with_ymd_and_hms(2021, 1, *d, 0, 0, 0).unwrap())
+            .collect();
+        let series = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+
+        // the windows ending on the 3rd, 6th and 7th cover (1, 3], (4, 6] and (5, 7]
+        assert_eq!(
+            duration_sma(Duration::days(2), &timestamps, &series),
+            Some(vec![2.5, 4.0, 4.5])
+        );
+        assert_eq!(
+            duration_sma(Duration::days(10), &timestamps, &series),
+            Some(vec![])
+        );
+        assert_eq!(
+            duration_sma(Duration::days(2), &timestamps[..2], &series),
+            None
+        );
+        assert_eq!(duration_sma(Duration::days(2), &[], &[]), None);
+    }
+
+    #[test]
+    fn test_window() {
+        assert_eq!("20".parse(), Ok(Window::Bars(20)));
+        assert_eq!("30d".parse(), Ok(Window::Duration(Duration::days(30))));
+        assert_eq!("2w".parse(), Ok(Window::Duration(Duration::weeks(2))));
+        assert_eq!("12h".parse(), Ok(Window::Duration(Duration::hours(12))));
+        assert!("0".parse::<Window>().is_err());
+        assert!("1".parse::<Window>().is_err());
+        assert!("1d".parse::<Window>().is_ok());
+        assert!("3y".parse::<Window>().is_err());
+
+        for s in &["20", "30d", "2w", "12h", "36h"] {
+            assert_eq!(s.parse::<Window>().unwrap().to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn test_indicator() {
+        assert_eq!("ema:20".parse(), Ok(Indicator::Ema(20)));
+        assert_eq!(
+            "SMA:30d".parse(),
+            Ok(Indicator::Sma(Window::Duration(Duration::days(30))))
+        );
+        assert_eq!("hma:9".parse::<Indicator>().unwrap().to_string(), "hma_9");
+        assert!("ema".parse::<Indicator>().is_err());
+        assert!("ema:1".parse::<Indicator>().is_err());
+        assert!("wma:3:4".parse::<Indicator>().is_err());
+        assert!("foo:3".parse::<Indicator>().is_err());
+        assert_eq!("rsi".parse(), Ok(Indicator::Rsi(14)));
+        assert_eq!(
+            "macd".parse(),
+            Ok(Indicator::Macd {
+                fast: 12,
+                slow: 26,
+                signal: 9
+            })
+        );
+        assert_eq!(
+            "macd:5:10:3".parse(),
+            Ok(Indicator::Macd {
+                fast: 5,
+                slow: 10,
+                signal: 3
+            })
+        );
+        assert!("macd:10:5:3".parse::<Indicator>().is_err());
+        assert_eq!(
+            "bb:10:2.5".parse(),
+            Ok(Indicator::Bollinger {
+                period: 10,
+                sigma: 2.5
+            })
+        );
+        assert_eq!(
+            "bb".parse(),
+            Ok(Indicator::Bollinger {
+                period: 20,
+                sigma: 2.0
+            })
+        );
+        assert!("bb:10:-1".parse::<Indicator>().is_err());
+        assert_eq!("atr".parse(), Ok(Indicator::Atr(14)));
+        assert_eq!(
+            "stoch:5:3".parse(),
+            Ok(Indicator::Stochastic { k: 5, d: 3 })
+        );
+        assert_eq!(
+            "adv:30d".parse::<Indicator>().unwrap().to_string(),
+            "adv_30d"
+        );
+        assert_eq!("obv".parse(), Ok(Indicator::Obv));
+        assert!("vwap:3".parse::<Indicator>().is_err());
+
+        let bars: Vec<Bar> = [1.0, 2.0, 3.0, 4.0]
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, i as u32 + 1, 0, 0, 0)
+                    .unwrap(),
+                open: *close,
+                high: *close,
+                low: *close,
+                close: *close,
+                adjclose: *close,
+                volume: 0,
+            })
+            .collect();
+        assert_eq!(
+            Indicator::Sma(Window::Bars(3)).compute(&bars),
+            vec![("sma_3".to_string(), vec![None, None, Some(2.0), Some(3.0)])]
+        );
+        assert_eq!(
+            Indicator::Ema(5).compute(&bars),
+            vec![("ema_5".to_string(), vec![None; 4])]
+        );
+        let names: Vec<String> = Indicator::Bollinger {
+            period: 2,
+            sigma: 2.0,
+        }
+        .compute(&bars)
+        .into_iter()
+        .map(|(name, values)| {
+            assert_eq!(values.len(), bars.len());
+            name
+        })
+        .collect();
+        assert_eq!(names, vec!["bb_upper_2_2", "bb_middle_2_2", "bb_lower_2_2"]);
+    }
+
+    #[test]
+    fn test_max() {
+        assert_eq!(max(&[]), None);
+        assert_eq!(max(&[1.0]), Some(1.0));
+        assert_eq!(max(&[1.0, 0.0]), Some(1.0));
+        assert_eq!(max(&[2.0, 3.0, 12.0, 9.0, 10.0]), Some(12.0));
+        assert_eq!(max(&[-2.0, -3.0]), Some(-2.0));
+    }
+
+    #[test]
+    fn test_min() {
+        assert_eq!(min(&[]), None);
+        assert_eq!(min(&[1.0]), Some(1.0));
+        assert_eq!(min(&[1.0, 0.0]), Some(0.0));
+        assert_eq!(min(&[2.0, 3.0, 12.0, 9.0, 10.0]), Some(2.0));
+        assert_eq!(min(&[-2.0, -3.0]), Some(-3.0));
+    }
+}
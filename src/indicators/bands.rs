@@ -0,0 +1,67 @@
+//!
+//! Volatility bands.
+//!
+
+///
+/// The bands of the Bollinger indicator, one value per complete window.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bollinger {
+    pub upper: Vec<f64>,
+    /// The simple moving average
+    pub middle: Vec<f64>,
+    pub lower: Vec<f64>,
+}
+
+///
+/// Calculate Bollinger bands `sigma` population standard deviations around the `n` bar simple
+/// moving average.
+///
+pub fn bollinger(n: usize, sigma: f64, series: &[f64]) -> Option<Bollinger> {
+    if series.is_empty() || n <= 1 {
+        return None;
+    }
+
+    let mut bands = Bollinger {
+        upper: vec![],
+        middle: vec![],
+        lower: vec![],
+    };
+    for window in series.windows(n) {
+        let mean = window.iter().sum::<f64>() / n as f64;
+        let variance = window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+        let width = sigma * variance.sqrt();
+
+        bands.upper.push(mean + width);
+        bands.middle.push(mean);
+        bands.lower.push(mean - width);
+    }
+    Some(bands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bollinger() {
+        // mean 5 and population standard deviation 2
+        let series = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let bands = bollinger(8, 2.0, &series).unwrap();
+        assert_eq!(bands.middle, vec![5.0]);
+        assert_eq!(bands.upper, vec![9.0]);
+        assert_eq!(bands.lower, vec![1.0]);
+
+        let bands = bollinger(2, 1.5, &[1.0, 3.0, 3.0]).unwrap();
+        assert_eq!(bands.middle, vec![2.0, 3.0]);
+        assert_eq!(bands.upper, vec![3.5, 3.0]);
+        assert_eq!(bands.lower, vec![0.5, 3.0]);
+
+        assert_eq!(
+            bollinger(5, 2.0, &series[..3]).unwrap().middle,
+            Vec::<f64>::new()
+        );
+        assert_eq!(bollinger(1, 2.0, &series), None);
+        assert_eq!(bollinger(20, 2.0, &[]), None);
+    }
+}
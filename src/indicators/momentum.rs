@@ -0,0 +1,199 @@
+//!
+//! Momentum oscillators.
+//!
+
+use super::moving_average::n_window_ema;
+
+///
+/// Calculate the relative strength index with Wilder's smoothing.
+///
+/// The first average gain and loss are the simple averages of the first `n` changes, later ones
+/// are smoothed with `(previous * (n - 1) + current) / n`. The first value belongs to the
+/// element at index `n`.
+///
+pub fn n_window_rsi(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n == 0 {
+        return None;
+    }
+    if series.len() <= n {
+        return Some(vec![]);
+    }
+
+    let changes: Vec<f64> = series.windows(2).map(|w| w[1] - w[0]).collect();
+    let mut gain = changes[..n].iter().map(|c| c.max(0.0)).sum::<f64>() / n as f64;
+    let mut loss = changes[..n].iter().map(|c| (-c).max(0.0)).sum::<f64>() / n as f64;
+
+    let rsi = |gain: f64, loss: f64| {
+        if loss == 0.0 {
+            100.0
+        } else {
+            100.0 - 100.0 / (1.0 + gain / loss)
+        }
+    };
+
+    let mut values = vec![rsi(gain, loss)];
+    for change in &changes[n..] {
+        gain = (gain * (n - 1) as f64 + change.max(0.0)) / n as f64;
+        loss = (loss * (n - 1) as f64 + (-change).max(0.0)) / n as f64;
+        values.push(rsi(gain, loss));
+    }
+    Some(values)
+}
+
+///
+/// The three lines of the moving average convergence/divergence indicator, aligned at their ends.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macd {
+    /// `EMA(fast) - EMA(slow)`
+    pub macd: Vec<f64>,
+    /// The `signal` bar EMA of the MACD line
+    pub signal: Vec<f64>,
+    /// `macd - signal`, as long as `signal`
+    pub histogram: Vec<f64>,
+}
+
+///
+/// Calculate the MACD line, its signal line and the histogram.
+///
+pub fn macd(fast: usize, slow: usize, signal: usize, series: &[f64]) -> Option<Macd> {
+    if fast >= slow {
+        return None;
+    }
+    let fast_ema = n_window_ema(fast, series)?;
+    let slow_ema = n_window_ema(slow, series)?;
+
+    let offset = fast_ema.len() - slow_ema.len();
+    let macd: Vec<f64> = slow_ema
+        .iter()
+        .zip(&fast_ema[offset..])
+        .map(|(slow, fast)| fast - slow)
+        .collect();
+    let signal = if macd.is_empty() {
+        vec![]
+    } else {
+        n_window_ema(signal, &macd)?
+    };
+    let histogram = signal
+        .iter()
+        .zip(&macd[macd.len() - signal.len()..])
+        .map(|(signal, macd)| macd - signal)
+        .collect();
+
+    Some(Macd {
+        macd,
+        signal,
+        histogram,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the relative strength index example from Wilder's "New Concepts in Technical Trading Systems"
+    // as reproduced by StockCharts, with the values TA-Lib computes for it
+    const CLOSES: [f64; 33] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+        46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35,
+        44.03, 44.18, 44.22, 44.57, 43.42, 42.66, 43.13,
+    ];
+
+    #[test]
+    fn test_n_window_rsi() {
+        let expected = [
+            70.46, 66.25, 66.48, 69.35, 66.29, 57.92, 62.88, 63.21, 56.01, 62.34, 54.67, 50.39,
+            40.02, 41.49, 41.90, 45.50, 37.32, 33.09, 37.79,
+        ];
+        let rsi = n_window_rsi(14, &CLOSES).unwrap();
+        assert_eq!(rsi.len(), expected.len());
+        for (actual, expected) in rsi.iter().zip(&expected) {
+            assert!(
+                (actual - expected).abs() < 0.005,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+
+        assert_eq!(n_window_rsi(3, &[1.0, 2.0, 3.0, 4.0]), Some(vec![100.0]));
+        assert_eq!(n_window_rsi(3, &[4.0, 3.0, 2.0, 1.0]), Some(vec![0.0]));
+        assert_eq!(n_window_rsi(14, &CLOSES[..14]), Some(vec![]));
+        assert_eq!(n_window_rsi(14, &[]), None);
+    }
+
+    // the exponential moving average example of StockCharts, whose published 10 day averages are
+    // the slow line of a 5/10/3 MACD; the expected values follow its spreadsheet from there
+    const EMA_CLOSES: [f64; 30] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38,
+        22.61, 23.36, 24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33,
+        22.68, 23.10, 22.40, 22.17,
+    ];
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_macd_reference() {
+        let result = macd(5, 10, 3, &EMA_CLOSES).unwrap();
+        assert_close(
+            &result.macd,
+            &[
+                0.0474, 0.0209, 0.0415, 0.0487, 0.0845, 0.2126, 0.3741, 0.3941, 0.3932, 0.3871,
+                0.3118, 0.2806, 0.2542, 0.1910, 0.0753, -0.0060, -0.0152, -0.1177, -0.1029,
+                -0.1946, -0.2677,
+            ],
+        );
+        assert_close(
+            &result.signal,
+            &[
+                0.0366, 0.0426, 0.0636, 0.1381, 0.2561, 0.3251, 0.3591, 0.3731, 0.3424, 0.3115,
+                0.2829, 0.2369, 0.1561, 0.0750, 0.0299, -0.0439, -0.0734, -0.1340, -0.2009,
+            ],
+        );
+        assert_close(
+            &result.histogram,
+            &[
+                0.0049, 0.0060, 0.0209, 0.0745, 0.1180, 0.0690, 0.0341, 0.0140, -0.0307, -0.0309,
+                -0.0287, -0.0459, -0.0808, -0.0811, -0.0451, -0.0738, -0.0295, -0.0606, -0.0669,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_macd() {
+        let result = macd(3, 6, 4, &CLOSES).unwrap();
+        assert_eq!(result.macd.len(), CLOSES.len() - 5);
+        assert_eq!(result.signal.len(), result.macd.len() - 3);
+        assert_eq!(result.histogram.len(), result.signal.len());
+
+        let fast = n_window_ema(3, &CLOSES).unwrap();
+        let slow = n_window_ema(6, &CLOSES).unwrap();
+        assert!((result.macd[0] - (fast[3] - slow[0])).abs() < 1e-12);
+        let last = result.macd.len() - 1;
+        assert!((result.macd[last] - (fast.last().unwrap() - slow.last().unwrap())).abs() < 1e-12);
+        assert!((result.histogram[0] - (result.macd[3] - result.signal[0])).abs() < 1e-12);
+
+        // a constant series has no momentum at all
+        let constant = macd(12, 26, 9, &[10.0; 40]).unwrap();
+        assert!(constant
+            .macd
+            .iter()
+            .chain(&constant.histogram)
+            .all(|v| v.abs() < 1e-12));
+        assert_eq!(constant.signal.len(), 7);
+
+        assert_eq!(macd(26, 12, 9, &CLOSES), None);
+        assert_eq!(macd(12, 26, 9, &[]), None);
+    }
+}
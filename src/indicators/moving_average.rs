@@ -0,0 +1,213 @@
+//!
+//! Exponential and weighted moving averages.
+//!
+//! Like `n_window_sma`, every function only returns values for complete windows, so the result
+//! is shorter than the input and its last value belongs to the last element of the series.
+//!
+
+///
+/// Calculate an exponential moving average with smoothing `2 / (n + 1)`, seeded with the simple
+/// average of the first `n` values.
+///
+pub fn n_window_ema(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n <= 1 {
+        return None;
+    }
+    if series.len() < n {
+        return Some(vec![]);
+    }
+
+    let alpha = 2.0 / (n as f64 + 1.0);
+    let seed = series[..n].iter().sum::<f64>() / n as f64;
+    let mut ema = Vec::with_capacity(series.len() - n + 1);
+    ema.push(seed);
+    for value in &series[n..] {
+        let previous = *ema.last().unwrap();
+        ema.push(alpha * value + (1.0 - alpha) * previous);
+    }
+    Some(ema)
+}
+
+///
+/// Calculate a linearly weighted moving average, the most recent value having weight `n`.
+///
+pub fn n_window_wma(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n <= 1 {
+        return None;
+    }
+
+    let total_weight = (n * (n + 1) / 2) as f64;
+    Some(
+        series
+            .windows(n)
+            .map(|w| {
+                w.iter()
+                    .enumerate()
+                    .map(|(i, value)| (i + 1) as f64 * value)
+                    .sum::<f64>()
+                    / total_weight
+            })
+            .collect(),
+    )
+}
+
+///
+/// Combines the tails of series of different length element-wise, aligned at their ends.
+///
+fn combine_tails(series: &[&[f64]], f: impl Fn(&[f64]) -> f64) -> Vec<f64> {
+    let len = series.iter().map(|s| s.len()).min().unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let values: Vec<f64> = series.iter().map(|s| s[s.len() - len + i]).collect();
+            f(&values)
+        })
+        .collect()
+}
+
+///
+/// Calculate a double exponential moving average: `2 * EMA - EMA(EMA)`.
+///
+pub fn n_window_dema(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    let ema = n_window_ema(n, series)?;
+    let ema_ema = n_window_ema(n, &ema).unwrap_or_default();
+    Some(combine_tails(&[&ema, &ema_ema], |v| 2.0 * v[0] - v[1]))
+}
+
+///
+/// Calculate a triple exponential moving average: `3 * EMA - 3 * EMA(EMA) + EMA(EMA(EMA))`.
+///
+pub fn n_window_tema(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    let ema = n_window_ema(n, series)?;
+    let ema_ema = n_window_ema(n, &ema).unwrap_or_default();
+    let ema_ema_ema = n_window_ema(n, &ema_ema).unwrap_or_default();
+    Some(combine_tails(&[&ema, &ema_ema, &ema_ema_ema], |v| {
+        3.0 * v[0] - 3.0 * v[1] + v[2]
+    }))
+}
+
+///
+/// Calculate a Hull moving average: `WMA(2 * WMA(n / 2) - WMA(n), sqrt(n))`.
+///
+pub fn n_window_hma(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n <= 1 {
+        return None;
+    }
+
+    let half = n_window_wma((n / 2).max(2), series)?;
+    let full = n_window_wma(n, series)?;
+    let raw = combine_tails(&[&half, &full], |v| 2.0 * v[0] - v[1]);
+    let sqrt_n = ((n as f64).sqrt().round() as usize).max(2);
+    if raw.is_empty() {
+        Some(vec![])
+    } else {
+        n_window_wma(sqrt_n, &raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<Vec<f64>>, expected: &[f64]) {
+        let actual = actual.unwrap();
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    const SERIES: [f64; 10] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29,
+    ];
+
+    #[test]
+    fn test_n_window_ema() {
+        assert_close(
+            n_window_ema(3, &SERIES),
+            &[
+                22.18,
+                22.175,
+                22.1775,
+                22.15375,
+                22.191875,
+                22.3109375,
+                22.27546875,
+                22.282734375,
+            ],
+        );
+        assert_close(n_window_ema(10, &SERIES), &[22.221]);
+        assert_eq!(n_window_ema(11, &SERIES), Some(vec![]));
+        assert_eq!(n_window_ema(1, &SERIES), None);
+        assert_eq!(n_window_ema(3, &[]), None);
+    }
+
+    #[test]
+    fn test_n_window_wma() {
+        assert_close(
+            n_window_wma(3, &[1.0, 2.0, 3.0, 4.0]),
+            &[14.0 / 6.0, 20.0 / 6.0],
+        );
+        assert_close(n_window_wma(4, &[4.0, 3.0, 2.0, 1.0]), &[2.0]);
+        assert_eq!(n_window_wma(5, &[1.0, 2.0]), Some(vec![]));
+        assert_eq!(n_window_wma(1, &SERIES), None);
+        assert_eq!(n_window_wma(3, &[]), None);
+    }
+
+    #[test]
+    fn test_n_window_dema() {
+        assert_close(
+            n_window_dema(3, &SERIES),
+            &[
+                22.1775,
+                22.141875,
+                22.205,
+                22.37703125,
+                22.29078125,
+                22.2940234375,
+            ],
+        );
+        // a linear series is tracked without lag
+        let linear: Vec<f64> = (0..20).map(|i| i as f64).collect();
+        assert_close(
+            n_window_dema(5, &linear).map(|d| d[d.len() - 1..].to_vec()),
+            &[19.0],
+        );
+        assert_eq!(n_window_dema(1, &SERIES), None);
+    }
+
+    #[test]
+    fn test_n_window_tema() {
+        let constant = [5.0; 12];
+        assert_close(n_window_tema(3, &constant), &[5.0; 6]);
+        assert_close(
+            n_window_tema(3, &SERIES),
+            &[
+                22.21333333333334,
+                22.407682291666674,
+                22.280716145833328,
+                22.28697916666666,
+            ],
+        );
+        assert_eq!(n_window_tema(5, &SERIES), Some(vec![]));
+        assert_eq!(n_window_tema(3, &[]), None);
+    }
+
+    #[test]
+    fn test_n_window_hma() {
+        let linear: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        // the Hull average of a linear series is the series itself
+        assert_close(n_window_hma(4, &linear), &[4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_close(
+            n_window_hma(4, &SERIES[..6]),
+            &[22.171222222222223, 22.161999999999995],
+        );
+        assert_eq!(n_window_hma(1, &SERIES), None);
+        assert_eq!(n_window_hma(20, &SERIES), Some(vec![]));
+    }
+}
@@ -0,0 +1,166 @@
+//!
+//! Indicators based on the high/low range of every bar.
+//!
+
+use super::n_window_sma;
+use crate::model::Bar;
+
+///
+/// Calculate the true range of every bar: the largest of its high/low range and the distances of
+/// its high and low from the previous close. The first bar has no previous close and uses its
+/// high/low range.
+///
+pub fn true_range(bars: &[Bar]) -> Option<Vec<f64>> {
+    if bars.is_empty() {
+        return None;
+    }
+
+    let first = bars[0].high - bars[0].low;
+    Some(
+        std::iter::once(first)
+            .chain(bars.windows(2).map(|w| {
+                let (previous, bar) = (&w[0], &w[1]);
+                (bar.high - bar.low)
+                    .max((bar.high - previous.close).abs())
+                    .max((bar.low - previous.close).abs())
+            }))
+            .collect(),
+    )
+}
+
+///
+/// Calculate the average true range with Wilder's smoothing, seeded with the simple average of
+/// the first `n` true ranges.
+///
+pub fn n_window_atr(n: usize, bars: &[Bar]) -> Option<Vec<f64>> {
+    let ranges = true_range(bars)?;
+    if n <= 1 {
+        return None;
+    }
+    if ranges.len() < n {
+        return Some(vec![]);
+    }
+
+    let mut atr = vec![ranges[..n].iter().sum::<f64>() / n as f64];
+    for range in &ranges[n..] {
+        let previous = *atr.last().unwrap();
+        atr.push((previous * (n - 1) as f64 + range) / n as f64);
+    }
+    Some(atr)
+}
+
+///
+/// The lines of the stochastic oscillator, aligned at their ends.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stochastic {
+    /// Where the close lies within the `k` bar high/low range, in percent
+    pub k: Vec<f64>,
+    /// The `d` bar simple moving average of `k`
+    pub d: Vec<f64>,
+}
+
+///
+/// Calculate the stochastic oscillator. A window without any range has a `%K` of 50.
+///
+pub fn stochastic(k: usize, d: usize, bars: &[Bar]) -> Option<Stochastic> {
+    if bars.is_empty() || k == 0 || d <= 1 {
+        return None;
+    }
+
+    let percent_k: Vec<f64> = bars
+        .windows(k)
+        .map(|w| {
+            let high = w.iter().fold(f64::MIN, |acc, b| acc.max(b.high));
+            let low = w.iter().fold(f64::MAX, |acc, b| acc.min(b.low));
+            let close = w[w.len() - 1].close;
+            if high > low {
+                100.0 * (close - low) / (high - low)
+            } else {
+                50.0
+            }
+        })
+        .collect();
+    let percent_d = if percent_k.is_empty() {
+        vec![]
+    } else {
+        n_window_sma(d, &percent_k)?
+    };
+
+    Some(Stochastic {
+        k: percent_k,
+        d: percent_d,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(hlc: &[(f64, f64, f64)]) -> Vec<Bar> {
+        hlc.iter()
+            .enumerate()
+            .map(|(i, (high, low, close))| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, i as u32 + 1, 0, 0, 0)
+                    .unwrap(),
+                open: *close,
+                high: *high,
+                low: *low,
+                close: *close,
+                adjclose: *close,
+                volume: 100,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_true_range() {
+        let bars = bars(&[
+            (10.0, 8.0, 9.0),
+            (9.5, 9.0, 9.25),
+            (12.0, 11.0, 11.5),
+            (11.0, 7.0, 8.0),
+        ]);
+        // inside bar, gap up above the previous close, wide range
+        assert_eq!(true_range(&bars), Some(vec![2.0, 0.5, 2.75, 4.5]));
+        assert_eq!(true_range(&[]), None);
+    }
+
+    #[test]
+    fn test_n_window_atr() {
+        let bars = bars(&[
+            (10.0, 8.0, 9.0),
+            (9.5, 9.0, 9.25),
+            (12.0, 11.0, 11.5),
+            (11.0, 7.0, 8.0),
+        ]);
+        let atr = n_window_atr(3, &bars).unwrap();
+        assert_eq!(atr.len(), 2);
+        assert!((atr[0] - 5.25 / 3.0).abs() < 1e-12);
+        assert!((atr[1] - (5.25 / 3.0 * 2.0 + 4.5) / 3.0).abs() < 1e-12);
+        assert_eq!(n_window_atr(5, &bars), Some(vec![]));
+        assert_eq!(n_window_atr(1, &bars), None);
+        assert_eq!(n_window_atr(3, &[]), None);
+    }
+
+    #[test]
+    fn test_stochastic() {
+        let bars = bars(&[
+            (10.0, 8.0, 9.0),
+            (11.0, 9.0, 11.0),
+            (12.0, 10.0, 10.0),
+            (10.0, 10.0, 10.0),
+        ]);
+        let stochastic = stochastic(2, 2, &bars).unwrap();
+        // ranges 8..11, 9..12 and 10..12
+        assert_eq!(stochastic.k, vec![100.0, 100.0 / 3.0, 0.0]);
+        assert_eq!(stochastic.d, vec![(100.0 + 100.0 / 3.0) / 2.0, 50.0 / 3.0]);
+
+        let flat = super::stochastic(2, 2, &bars[3..]).unwrap();
+        assert!(flat.k.is_empty() && flat.d.is_empty());
+        assert_eq!(super::stochastic(1, 2, &bars[3..]).unwrap().k, vec![50.0]);
+        assert_eq!(super::stochastic(2, 2, &[]), None);
+    }
+}
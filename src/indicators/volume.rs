@@ -0,0 +1,101 @@
+//!
+//! Indicators based on traded volume.
+//!
+
+use crate::model::Bar;
+
+///
+/// Calculate the volume weighted average of the typical price `(high + low + close) / 3`,
+/// cumulated from the first bar.
+///
+/// Bars before the first traded volume have no average and are left out.
+///
+pub fn vwap(bars: &[Bar]) -> Option<Vec<f64>> {
+    if bars.is_empty() {
+        return None;
+    }
+
+    let (mut value, mut volume) = (0.0, 0.0);
+    let mut averages = Vec::new();
+    for bar in bars {
+        value += (bar.high + bar.low + bar.close) / 3.0 * bar.volume as f64;
+        volume += bar.volume as f64;
+        if volume > 0.0 {
+            averages.push(value / volume);
+        }
+    }
+    Some(averages)
+}
+
+///
+/// Calculate the on-balance volume: the running total of volume, added on bars closing higher and
+/// subtracted on bars closing lower than the previous one. It starts at 0 with the first bar.
+///
+pub fn obv(bars: &[Bar]) -> Option<Vec<f64>> {
+    if bars.is_empty() {
+        return None;
+    }
+
+    let mut total = 0.0;
+    Some(
+        std::iter::once(0.0)
+            .chain(bars.windows(2).map(|w| {
+                if w[1].close > w[0].close {
+                    total += w[1].volume as f64;
+                } else if w[1].close < w[0].close {
+                    total -= w[1].volume as f64;
+                }
+                total
+            }))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn bars(ohlcv: &[(f64, f64, f64, u64)]) -> Vec<Bar> {
+        ohlcv
+            .iter()
+            .enumerate()
+            .map(|(i, (high, low, close, volume))| Bar {
+                timestamp: Utc
+                    .with_ymd_and_hms(2021, 1, i as u32 + 1, 0, 0, 0)
+                    .unwrap(),
+                open: *close,
+                high: *high,
+                low: *low,
+                close: *close,
+                adjclose: *close,
+                volume: *volume,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_vwap() {
+        let bars = bars(&[
+            (3.0, 3.0, 3.0, 0),
+            (12.0, 9.0, 9.0, 100),
+            (7.0, 5.0, 6.0, 300),
+        ]);
+        assert_eq!(vwap(&bars), Some(vec![10.0, 7.0]));
+        assert_eq!(vwap(&bars[..1]), Some(vec![]));
+        assert_eq!(vwap(&[]), None);
+    }
+
+    #[test]
+    fn test_obv() {
+        let bars = bars(&[
+            (1.0, 1.0, 10.0, 100),
+            (1.0, 1.0, 11.0, 200),
+            (1.0, 1.0, 11.0, 300),
+            (1.0, 1.0, 9.0, 400),
+            (1.0, 1.0, 12.0, 50),
+        ]);
+        assert_eq!(obv(&bars), Some(vec![0.0, 200.0, 200.0, -200.0, -150.0]));
+        assert_eq!(obv(&[]), None);
+    }
+}
@@ -0,0 +1,543 @@
+//!
+//! A ledger of transactions replayed into tax lots, with gains realized by a lot matching method.
+//!
+
+use crate::error::{Error, Result};
+use crate::provider::csv_file::parse_timestamp;
+use chrono::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+pub mod report;
+
+/// Remaining lot quantities below this are treated as sold out.
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    /// Cash paid out for a held symbol
+    Dividend,
+    /// A stock split, with the number of new shares per old share as the quantity
+    Split,
+    /// A cost that is not part of any lot, e.g. a custody fee
+    Fee,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionKind::Buy => write!(f, "buy"),
+            TransactionKind::Sell => write!(f, "sell"),
+            TransactionKind::Dividend => write!(f, "dividend"),
+            TransactionKind::Split => write!(f, "split"),
+            TransactionKind::Fee => write!(f, "fee"),
+        }
+    }
+}
+
+impl FromStr for TransactionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "buy" => Ok(TransactionKind::Buy),
+            "sell" => Ok(TransactionKind::Sell),
+            "dividend" => Ok(TransactionKind::Dividend),
+            "split" => Ok(TransactionKind::Split),
+            "fee" => Ok(TransactionKind::Fee),
+            _ => Err(format!("unknown transaction type '{}'", s)),
+        }
+    }
+}
+
+///
+/// How the shares of a sale are matched to the lots they came from.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LotMethod {
+    /// The oldest lots are sold first
+    Fifo,
+    /// The newest lots are sold first
+    Lifo,
+    /// All lots of a symbol share their average cost and are sold proportionally
+    Average,
+    /// Every sale names the lot it is sold from
+    Specific,
+}
+
+impl FromStr for LotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fifo" => Ok(LotMethod::Fifo),
+            "lifo" => Ok(LotMethod::Lifo),
+            "average" | "avg" => Ok(LotMethod::Average),
+            "specific" => Ok(LotMethod::Specific),
+            _ => Err(format!(
+                "unknown lot method '{}', expected fifo, lifo, average or specific",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub date: DateTime<Utc>,
+    pub kind: TransactionKind,
+    /// Empty for fees not tied to a symbol
+    pub symbol: String,
+    /// The number of shares bought or sold, or the split ratio
+    pub quantity: f64,
+    /// The price per share of buys and sells
+    pub price: f64,
+    /// The cash amount of dividends and fees
+    pub amount: f64,
+    /// The lot a buy opens or a sale is taken from
+    pub lot: Option<String>,
+}
+
+///
+/// One transaction as written in a ledger file.
+///
+#[derive(Debug, Deserialize)]
+struct TransactionRow {
+    date: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    quantity: Option<f64>,
+    #[serde(default)]
+    price: Option<f64>,
+    #[serde(default)]
+    amount: Option<f64>,
+    #[serde(default)]
+    lot: Option<String>,
+}
+
+impl TransactionRow {
+    fn into_transaction(self) -> Result<Transaction> {
+        let kind: TransactionKind = self.kind.parse().map_err(Error::Parse)?;
+        let date = parse_timestamp(&self.date)?;
+        let symbol = self.symbol.trim().to_string();
+        let invalid = |what: &str| {
+            Error::Parse(format!(
+                "{} on {} for '{}' needs {}",
+                kind,
+                date.format("%Y-%m-%d"),
+                symbol,
+                what
+            ))
+        };
+
+        if kind != TransactionKind::Fee && symbol.is_empty() {
+            return Err(invalid("a symbol"));
+        }
+        let positive = |value: Option<f64>| value.filter(|v| v.is_finite() && *v > 0.0);
+        let (quantity, price, amount) = match kind {
+            TransactionKind::Buy | TransactionKind::Sell => (
+                positive(self.quantity).ok_or_else(|| invalid("a positive quantity"))?,
+                self.price
+                    .filter(|p| p.is_finite() && *p >= 0.0)
+                    .ok_or_else(|| invalid("a price"))?,
+                0.0,
+            ),
+            TransactionKind::Split => (
+                positive(self.quantity).ok_or_else(|| invalid("the split ratio as quantity"))?,
+                0.0,
+                0.0,
+            ),
+            TransactionKind::Dividend | TransactionKind::Fee => (
+                0.0,
+                0.0,
+                self.amount
+                    .filter(|a| a.is_finite() && *a >= 0.0)
+                    .ok_or_else(|| invalid("an amount"))?,
+            ),
+        };
+
+        Ok(Transaction {
+            date,
+            kind,
+            symbol,
+            quantity,
+            price,
+            amount,
+            lot: self
+                .lot
+                .map(|lot| lot.trim().to_string())
+                .filter(|lot| !lot.is_empty()),
+        })
+    }
+}
+
+///
+/// The transactions of an account, sorted by date.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ledger {
+    pub transactions: Vec<Transaction>,
+}
+
+impl Ledger {
+    ///
+    /// Reads a CSV ledger with the columns `date`, `type` and the optional `symbol`, `quantity`,
+    /// `price`, `amount` and `lot`. Transactions on the same date are kept in file order.
+    ///
+    pub fn load(path: &Path) -> Result<Ledger> {
+        Ledger::from_csv(std::fs::File::open(path)?)
+    }
+
+    pub fn from_csv<R: Read>(reader: R) -> Result<Ledger> {
+        let mut transactions = Vec::new();
+        for row in csv::Reader::from_reader(reader).deserialize() {
+            let row: TransactionRow = row?;
+            transactions.push(row.into_transaction()?);
+        }
+        transactions.sort_by_key(|t| t.date);
+        Ok(Ledger { transactions })
+    }
+
+    ///
+    /// The symbols of the ledger without duplicates, in the order they first appear.
+    ///
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = Vec::new();
+        for transaction in &self.transactions {
+            if !transaction.symbol.is_empty() && !symbols.contains(&transaction.symbol) {
+                symbols.push(transaction.symbol.clone());
+            }
+        }
+        symbols
+    }
+
+    ///
+    /// Replays all transactions, matching sales to lots with `method`.
+    ///
+    /// # Returns
+    /// An error if a sale exceeds the shares held or names a lot that cannot cover it.
+    ///
+    pub fn replay(&self, method: LotMethod) -> Result<Replay> {
+        let mut replay = Replay::default();
+        for transaction in &self.transactions {
+            replay.apply(transaction, method)?;
+        }
+        Ok(replay)
+    }
+}
+
+///
+/// Shares bought together, with what is left of them.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lot {
+    pub id: String,
+    pub symbol: String,
+    pub acquired: DateTime<Utc>,
+    /// The shares not sold yet
+    pub quantity: f64,
+    /// The cost per share, adjusted for splits and, with average cost matching, averaged
+    pub cost_basis: f64,
+}
+
+///
+/// The part of a lot closed by a sale.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    pub symbol: String,
+    pub lot: String,
+    pub acquired: DateTime<Utc>,
+    pub sold: DateTime<Utc>,
+    pub quantity: f64,
+    pub cost: f64,
+    pub proceeds: f64,
+}
+
+impl Disposal {
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+}
+
+///
+/// The position of a symbol right after a transaction.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub transaction: Transaction,
+    pub position: f64,
+    pub position_cost: f64,
+    /// The gain realized by this transaction
+    pub realized_gain: f64,
+}
+
+///
+/// The state of the account after replaying a ledger.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    /// The lots that still hold shares, in order of acquisition
+    pub lots: Vec<Lot>,
+    pub disposals: Vec<Disposal>,
+    pub dividends: HashMap<String, f64>,
+    /// Fees by symbol, with fees not tied to a symbol under the empty symbol
+    pub fees: HashMap<String, f64>,
+    /// Every transaction with the position it left
+    pub steps: Vec<Step>,
+    lot_counts: HashMap<String, usize>,
+}
+
+impl Replay {
+    fn apply(&mut self, transaction: &Transaction, method: LotMethod) -> Result<()> {
+        let symbol = &transaction.symbol;
+        let disposals_before = self.disposals.len();
+        match transaction.kind {
+            TransactionKind::Buy => self.buy(transaction, method)?,
+            TransactionKind::Sell => self.sell(transaction, method)?,
+            TransactionKind::Dividend => {
+                *self.dividends.entry(symbol.clone()).or_default() += transaction.amount;
+            }
+            TransactionKind::Split => {
+                for lot in self.lots.iter_mut().filter(|l| &l.symbol == symbol) {
+                    lot.quantity *= transaction.quantity;
+                    lot.cost_basis /= transaction.quantity;
+                }
+            }
+            TransactionKind::Fee => {
+                *self.fees.entry(symbol.clone()).or_default() += transaction.amount;
+            }
+        }
+
+        // folded from +0.0, as an empty float sum is -0.0
+        let held = self.lots.iter().filter(|l| &l.symbol == symbol);
+        self.steps.push(Step {
+            transaction: transaction.clone(),
+            position: held.clone().fold(0.0, |sum, l| sum + l.quantity),
+            position_cost: held.fold(0.0, |sum, l| sum + l.quantity * l.cost_basis),
+            realized_gain: self.disposals[disposals_before..]
+                .iter()
+                .fold(0.0, |sum, d| sum + d.gain()),
+        });
+        Ok(())
+    }
+
+    fn buy(&mut self, transaction: &Transaction, method: LotMethod) -> Result<()> {
+        let symbol = &transaction.symbol;
+        let count = self.lot_counts.entry(symbol.clone()).or_default();
+        *count += 1;
+        let id = match &transaction.lot {
+            Some(lot) => lot.clone(),
+            None => format!("{}-{}", symbol, count),
+        };
+        if self.lots.iter().any(|l| &l.symbol == symbol && l.id == id)
+            || self
+                .disposals
+                .iter()
+                .any(|d| &d.symbol == symbol && d.lot == id)
+        {
+            return Err(Error::Parse(format!(
+                "lot '{}' of '{}' is opened twice",
+                id, symbol
+            )));
+        }
+
+        self.lots.push(Lot {
+            id,
+            symbol: symbol.clone(),
+            acquired: transaction.date,
+            quantity: transaction.quantity,
+            cost_basis: transaction.price,
+        });
+
+        if method == LotMethod::Average {
+            let held = self.lots.iter().filter(|l| &l.symbol == symbol);
+            let quantity: f64 = held.clone().map(|l| l.quantity).sum();
+            let cost: f64 = held.map(|l| l.quantity * l.cost_basis).sum();
+            for lot in self.lots.iter_mut().filter(|l| &l.symbol == symbol) {
+                lot.cost_basis = cost / quantity;
+            }
+        }
+        Ok(())
+    }
+
+    fn sell(&mut self, transaction: &Transaction, method: LotMethod) -> Result<()> {
+        let symbol = &transaction.symbol;
+        let held: Vec<usize> = (0..self.lots.len())
+            .filter(|i| &self.lots[*i].symbol == symbol)
+            .collect();
+        let available: f64 = held.iter().map(|i| self.lots[*i].quantity).sum();
+        let date = transaction.date.format("%Y-%m-%d");
+        if transaction.quantity > available + EPSILON {
+            return Err(Error::Parse(format!(
+                "selling {} '{}' on {} but only {} are held",
+                transaction.quantity, symbol, date, available
+            )));
+        }
+
+        // the quantity taken from every matched lot
+        let taken: Vec<(usize, f64)> = match (&transaction.lot, method) {
+            (Some(id), _) => {
+                let i = *held
+                    .iter()
+                    .find(|i| &self.lots[**i].id == id)
+                    .ok_or_else(|| {
+                        Error::Parse(format!(
+                            "selling '{}' on {} from lot '{}', which is not held",
+                            symbol, date, id
+                        ))
+                    })?;
+                if transaction.quantity > self.lots[i].quantity + EPSILON {
+                    return Err(Error::Parse(format!(
+                        "selling {} '{}' on {} from lot '{}', which only holds {}",
+                        transaction.quantity, symbol, date, id, self.lots[i].quantity
+                    )));
+                }
+                vec![(i, transaction.quantity)]
+            }
+            (None, LotMethod::Specific) => {
+                return Err(Error::Parse(format!(
+                    "selling '{}' on {} without a lot, which specific lot matching requires",
+                    symbol, date
+                )))
+            }
+            (None, LotMethod::Average) => held
+                .iter()
+                .map(|i| {
+                    (
+                        *i,
+                        transaction.quantity * self.lots[*i].quantity / available,
+                    )
+                })
+                .collect(),
+            (None, LotMethod::Fifo) | (None, LotMethod::Lifo) => {
+                let order: Box<dyn Iterator<Item = &usize>> = if method == LotMethod::Fifo {
+                    Box::new(held.iter())
+                } else {
+                    Box::new(held.iter().rev())
+                };
+                let mut remaining = transaction.quantity;
+                let mut taken = Vec::new();
+                for i in order {
+                    if remaining <= EPSILON {
+                        break;
+                    }
+                    let quantity = remaining.min(self.lots[*i].quantity);
+                    taken.push((*i, quantity));
+                    remaining -= quantity;
+                }
+                taken
+            }
+        };
+
+        for (i, quantity) in taken {
+            let lot = &mut self.lots[i];
+            lot.quantity -= quantity;
+            self.disposals.push(Disposal {
+                symbol: symbol.clone(),
+                lot: lot.id.clone(),
+                acquired: lot.acquired,
+                sold: transaction.date,
+                quantity,
+                cost: quantity * lot.cost_basis,
+                proceeds: quantity * transaction.price,
+            });
+        }
+        self.lots.retain(|l| l.quantity > EPSILON);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEDGER: &str = "date,type,symbol,quantity,price,amount,lot\n\
+                          2020-01-02,buy,MSFT,10,100,,\n\
+                          2020-06-01,buy,MSFT,10,150,,\n\
+                          2020-07-01,dividend,MSFT,,,5.5,\n\
+                          2020-09-01,fee,,,,2,\n\
+                          2021-03-01,sell,MSFT,15,200,,\n";
+
+    fn replay(ledger: &str, method: LotMethod) -> Result<Replay> {
+        Ledger::from_csv(ledger.as_bytes())?.replay(method)
+    }
+
+    fn realized(replay: &Replay) -> f64 {
+        replay.disposals.iter().map(Disposal::gain).sum()
+    }
+
+    #[test]
+    fn test_parse_ledger() {
+        let ledger = Ledger::from_csv(LEDGER.as_bytes()).unwrap();
+        assert_eq!(ledger.transactions.len(), 5);
+        assert_eq!(ledger.transactions[2].kind, TransactionKind::Dividend);
+        assert_eq!(ledger.transactions[2].amount, 5.5);
+        assert_eq!(ledger.symbols(), vec!["MSFT"]);
+
+        assert!(Ledger::from_csv("date,type,symbol\n2020-01-02,buy,MSFT\n".as_bytes()).is_err());
+        assert!(Ledger::from_csv("date,type\n2020-01-02,gift\n".as_bytes()).is_err());
+        assert!(Ledger::from_csv("date,type,amount\n2020-01-02,dividend,1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_fifo_and_lifo() {
+        let fifo = replay(LEDGER, LotMethod::Fifo).unwrap();
+        // 10 from the first lot at 100, 5 from the second at 150
+        assert_eq!(realized(&fifo), 10.0 * 100.0 + 5.0 * 50.0);
+        assert_eq!(fifo.lots.len(), 1);
+        assert_eq!(fifo.lots[0].id, "MSFT-2");
+        assert_eq!(fifo.lots[0].quantity, 5.0);
+        assert_eq!(fifo.dividends["MSFT"], 5.5);
+        assert_eq!(fifo.fees[""], 2.0);
+
+        let lifo = replay(LEDGER, LotMethod::Lifo).unwrap();
+        assert_eq!(realized(&lifo), 10.0 * 50.0 + 5.0 * 100.0);
+        assert_eq!(lifo.lots[0].id, "MSFT-1");
+        assert_eq!(lifo.steps.last().unwrap().position, 5.0);
+        assert_eq!(lifo.steps.last().unwrap().position_cost, 500.0);
+    }
+
+    #[test]
+    fn test_average_cost() {
+        let average = replay(LEDGER, LotMethod::Average).unwrap();
+        assert!((realized(&average) - 15.0 * 75.0).abs() < 1e-9);
+        assert_eq!(average.lots.len(), 2);
+        assert!(average.lots.iter().all(|l| l.cost_basis == 125.0));
+        assert!((average.lots.iter().map(|l| l.quantity).sum::<f64>() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_specific_lots_and_splits() {
+        let ledger = "date,type,symbol,quantity,price,amount,lot\n\
+                      2020-01-02,buy,AAPL,10,300,,cheap\n\
+                      2020-03-02,buy,AAPL,10,400,,dear\n\
+                      2020-08-31,split,AAPL,4,,,\n\
+                      2021-01-04,sell,AAPL,20,130,,dear\n";
+        let specific = replay(ledger, LotMethod::Specific).unwrap();
+        // 10 shares at 400 became 40 at 100
+        assert_eq!(specific.disposals[0].lot, "dear");
+        assert_eq!(specific.disposals[0].cost, 2000.0);
+        assert_eq!(realized(&specific), 20.0 * 30.0);
+        let quantities: Vec<f64> = specific.lots.iter().map(|l| l.quantity).collect();
+        assert_eq!(quantities, vec![40.0, 20.0]);
+
+        let unnamed = "date,type,symbol,quantity,price\n\
+                       2020-01-02,buy,AAPL,10,300\n\
+                       2020-02-02,sell,AAPL,1,300\n";
+        assert!(replay(unnamed, LotMethod::Specific).is_err());
+        let oversold = "date,type,symbol,quantity,price\n\
+                        2020-01-02,buy,AAPL,10,300\n\
+                        2020-02-02,sell,AAPL,11,300\n";
+        assert!(replay(oversold, LotMethod::Fifo).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//!
+//! The reports of a replayed ledger, valued at the latest quotes.
+//!
+
+use super::{Replay, Step};
+use crate::model::Bar;
+use crate::output::{Field, Record};
+use crate::portfolio::TOTAL;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Lots held longer than this many days are taxed as long term gains.
+const LONG_TERM_DAYS: i64 = 365;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedgerReport {
+    /// One row per symbol with realized and unrealized gains
+    Positions,
+    /// One row per open lot and per sale of a lot
+    Lots,
+    /// One row per transaction with the position it left
+    History,
+}
+
+impl FromStr for LedgerReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "positions" => Ok(LedgerReport::Positions),
+            "lots" => Ok(LedgerReport::Lots),
+            "history" => Ok(LedgerReport::History),
+            _ => Err(format!(
+                "unknown report '{}', expected positions, lots or history",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// The latest close of every symbol with its timestamp.
+///
+fn latest_closes(history: &HashMap<String, Vec<Bar>>) -> HashMap<&str, (DateTime<Utc>, f64)> {
+    history
+        .iter()
+        .filter_map(|(symbol, bars)| Some((symbol.as_str(), bars.last()?)))
+        .map(|(symbol, bar)| (symbol, (bar.timestamp, bar.close)))
+        .collect()
+}
+
+///
+/// The gains of a symbol over the whole ledger, or of the account in the total row.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionSummary {
+    pub symbol: String,
+    pub quantity: Option<f64>,
+    /// The cost of the shares still held
+    pub cost: f64,
+    pub price: Option<f64>,
+    /// `None` if a held symbol has no quote
+    pub market_value: Option<f64>,
+    pub unrealized_gain: Option<f64>,
+    pub realized_gain: f64,
+    pub dividends: f64,
+    pub fees: f64,
+    /// Realized and unrealized gains plus dividends minus fees
+    pub total_gain: Option<f64>,
+}
+
+impl Record for PositionSummary {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("quantity".to_string(), Field::Number(self.quantity)),
+            ("cost".to_string(), Field::Number(Some(self.cost))),
+            ("price".to_string(), Field::Number(self.price)),
+            ("market_value".to_string(), Field::Number(self.market_value)),
+            (
+                "unrealized_gain".to_string(),
+                Field::Number(self.unrealized_gain),
+            ),
+            (
+                "realized_gain".to_string(),
+                Field::Number(Some(self.realized_gain)),
+            ),
+            ("dividends".to_string(), Field::Number(Some(self.dividends))),
+            ("fees".to_string(), Field::Number(Some(self.fees))),
+            ("total_gain".to_string(), Field::Number(self.total_gain)),
+        ]
+    }
+}
+
+///
+/// Sums up every symbol of `symbols`, valuing the shares still held at the latest close in
+/// `history`, and appends a total row that also holds the fees not tied to a symbol.
+///
+pub fn positions(
+    replay: &Replay,
+    symbols: &[String],
+    history: &HashMap<String, Vec<Bar>>,
+) -> Vec<PositionSummary> {
+    let closes = latest_closes(history);
+    let mut rows: Vec<PositionSummary> = symbols
+        .iter()
+        .map(|symbol| {
+            // folded from +0.0, as an empty float sum is -0.0
+            let lots = replay.lots.iter().filter(|l| &l.symbol == symbol);
+            let quantity = lots.clone().fold(0.0, |sum, l| sum + l.quantity);
+            let cost = lots.fold(0.0, |sum, l| sum + l.quantity * l.cost_basis);
+            let price = closes.get(symbol.as_str()).map(|(_, close)| *close);
+            let market_value = match price {
+                Some(price) => Some(quantity * price),
+                None if quantity == 0.0 => Some(0.0),
+                None => None,
+            };
+            let unrealized_gain = market_value.map(|value| value - cost);
+            let realized_gain = replay
+                .disposals
+                .iter()
+                .filter(|d| &d.symbol == symbol)
+                .fold(0.0, |sum, d| sum + d.gain());
+            let dividends = replay.dividends.get(symbol).copied().unwrap_or_default();
+            let fees = replay.fees.get(symbol).copied().unwrap_or_default();
+
+            PositionSummary {
+                symbol: symbol.clone(),
+                quantity: Some(quantity),
+                cost,
+                price,
+                market_value,
+                unrealized_gain,
+                realized_gain,
+                dividends,
+                fees,
+                total_gain: unrealized_gain.map(|gain| gain + realized_gain + dividends - fees),
+            }
+        })
+        .collect();
+
+    let market_value: Option<f64> = rows.iter().map(|r| r.market_value).sum();
+    let cost = rows.iter().map(|r| r.cost).sum();
+    let realized_gain = rows.iter().map(|r| r.realized_gain).sum();
+    let dividends = rows.iter().map(|r| r.dividends).sum();
+    let fees = replay.fees.values().sum();
+    let unrealized_gain = market_value.map(|value| value - cost);
+    rows.push(PositionSummary {
+        symbol: TOTAL.to_string(),
+        quantity: None,
+        cost,
+        price: None,
+        market_value,
+        unrealized_gain,
+        realized_gain,
+        dividends,
+        fees,
+        total_gain: unrealized_gain.map(|gain| gain + realized_gain + dividends - fees),
+    });
+    rows
+}
+
+///
+/// A lot or a part of it: either sold, or still open and valued at the latest close.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotRow {
+    pub symbol: String,
+    pub lot: String,
+    /// Whether the shares are `open` or `closed`
+    pub status: &'static str,
+    pub acquired: DateTime<Utc>,
+    /// The date of the sale, or of the latest close open lots are valued at
+    pub end: DateTime<Utc>,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub cost: f64,
+    /// The proceeds of the sale or the market value, `None` if an open lot has no quote
+    pub value: Option<f64>,
+    pub gain: Option<f64>,
+    pub holding_days: i64,
+    /// `long` if held for more than a year, `short` otherwise
+    pub term: &'static str,
+}
+
+impl LotRow {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        symbol: &str,
+        lot: &str,
+        status: &'static str,
+        acquired: DateTime<Utc>,
+        end: DateTime<Utc>,
+        quantity: f64,
+        cost: f64,
+        value: Option<f64>,
+    ) -> LotRow {
+        let holding_days = (end - acquired).num_days();
+        LotRow {
+            symbol: symbol.to_string(),
+            lot: lot.to_string(),
+            status,
+            acquired,
+            end,
+            quantity,
+            cost_basis: cost / quantity,
+            cost,
+            value,
+            gain: value.map(|value| value - cost),
+            holding_days,
+            term: if holding_days > LONG_TERM_DAYS {
+                "long"
+            } else {
+                "short"
+            },
+        }
+    }
+}
+
+impl Record for LotRow {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("lot".to_string(), Field::Text(self.lot.clone())),
+            ("status".to_string(), Field::Text(self.status.to_string())),
+            ("acquired".to_string(), Field::Timestamp(self.acquired)),
+            ("end".to_string(), Field::Timestamp(self.end)),
+            ("quantity".to_string(), Field::Number(Some(self.quantity))),
+            (
+                "cost_basis".to_string(),
+                Field::Number(Some(self.cost_basis)),
+            ),
+            ("cost".to_string(), Field::Number(Some(self.cost))),
+            ("value".to_string(), Field::Number(self.value)),
+            ("gain".to_string(), Field::Number(self.gain)),
+            (
+                "holding_days".to_string(),
+                Field::Number(Some(self.holding_days as f64)),
+            ),
+            ("term".to_string(), Field::Text(self.term.to_string())),
+        ]
+    }
+}
+
+///
+/// Lists every sale of a lot, followed by the open lots valued at the latest close in `history`,
+/// or as of `now` if a symbol has no quotes.
+///
+pub fn lots(
+    replay: &Replay,
+    history: &HashMap<String, Vec<Bar>>,
+    now: DateTime<Utc>,
+) -> Vec<LotRow> {
+    let closes = latest_closes(history);
+    let closed = replay.disposals.iter().map(|d| {
+        LotRow::new(
+            &d.symbol,
+            &d.lot,
+            "closed",
+            d.acquired,
+            d.sold,
+            d.quantity,
+            d.cost,
+            Some(d.proceeds),
+        )
+    });
+    let open = replay.lots.iter().map(|l| {
+        let quote = closes.get(l.symbol.as_str());
+        LotRow::new(
+            &l.symbol,
+            &l.id,
+            "open",
+            l.acquired,
+            quote.map_or(now, |(timestamp, _)| *timestamp),
+            l.quantity,
+            l.quantity * l.cost_basis,
+            quote.map(|(_, close)| l.quantity * close),
+        )
+    });
+    closed.chain(open).collect()
+}
+
+impl Record for Step {
+    fn fields(&self) -> Vec<(String, Field)> {
+        use super::TransactionKind::*;
+        let transaction = &self.transaction;
+        let has_price = matches!(transaction.kind, Buy | Sell);
+        let has_quantity = has_price || transaction.kind == Split;
+        vec![
+            ("date".to_string(), Field::Timestamp(transaction.date)),
+            (
+                "type".to_string(),
+                Field::Text(transaction.kind.to_string()),
+            ),
+            (
+                "symbol".to_string(),
+                Field::Text(transaction.symbol.clone()),
+            ),
+            (
+                "quantity".to_string(),
+                Field::Number(Some(transaction.quantity).filter(|_| has_quantity)),
+            ),
+            (
+                "price".to_string(),
+                Field::Number(Some(transaction.price).filter(|_| has_price)),
+            ),
+            ("cash".to_string(), Field::Number(Some(self.cash()))),
+            ("position".to_string(), Field::Number(Some(self.position))),
+            (
+                "position_cost".to_string(),
+                Field::Number(Some(self.position_cost)),
+            ),
+            (
+                "realized_gain".to_string(),
+                Field::Number(Some(self.realized_gain)),
+            ),
+        ]
+    }
+}
+
+impl Step {
+    ///
+    /// The cash flow of the transaction: negative for buys and fees, positive for sales and
+    /// dividends.
+    ///
+    pub fn cash(&self) -> f64 {
+        use super::TransactionKind::*;
+        let transaction = &self.transaction;
+        match transaction.kind {
+            Buy => -transaction.quantity * transaction.price,
+            Sell => transaction.quantity * transaction.price,
+            Dividend => transaction.amount,
+            Fee => -transaction.amount,
+            Split => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Ledger, LotMethod};
+    use super::*;
+
+    fn history(symbol: &str, close: f64) -> HashMap<String, Vec<Bar>> {
+        let mut history = HashMap::new();
+        history.insert(
+            symbol.to_string(),
+            vec![Bar {
+                timestamp: Utc.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap(),
+                open: close,
+                high: close,
+                low: close,
+                close,
+                adjclose: close,
+                volume: 0,
+            }],
+        );
+        history
+    }
+
+    fn replay() -> Replay {
+        let ledger = "date,type,symbol,quantity,price,amount,lot\n\
+                      2020-01-02,buy,MSFT,10,100,,\n\
+                      2020-06-01,buy,MSFT,10,150,,\n\
+                      2020-07-01,dividend,MSFT,,,5.5,\n\
+                      2020-09-01,fee,,,,2,\n\
+                      2021-03-01,sell,MSFT,15,200,,\n";
+        Ledger::from_csv(ledger.as_bytes())
+            .unwrap()
+            .replay(LotMethod::Fifo)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_positions() {
+        let rows = positions(&replay(), &["MSFT".to_string()], &history("MSFT", 210.0));
+        assert_eq!(rows.len(), 2);
+        let msft = &rows[0];
+        assert_eq!(msft.quantity, Some(5.0));
+        assert_eq!(msft.cost, 750.0);
+        assert_eq!(msft.market_value, Some(1050.0));
+        assert_eq!(msft.unrealized_gain, Some(300.0));
+        assert_eq!(msft.realized_gain, 1250.0);
+        assert_eq!(msft.total_gain, Some(1555.5));
+
+        let total = &rows[1];
+        assert_eq!(total.symbol, TOTAL);
+        assert_eq!(total.fees, 2.0);
+        assert_eq!(total.total_gain, Some(1553.5));
+
+        let unquoted = positions(&replay(), &["MSFT".to_string()], &HashMap::new());
+        assert_eq!(unquoted[1].market_value, None);
+    }
+
+    #[test]
+    fn test_lots() {
+        let now = Utc.with_ymd_and_hms(2021, 6, 2, 0, 0, 0).unwrap();
+        let rows = lots(&replay(), &history("MSFT", 210.0), now);
+        let summary: Vec<(&str, &str, f64, Option<f64>, &str)> = rows
+            .iter()
+            .map(|r| (r.lot.as_str(), r.status, r.quantity, r.gain, r.term))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("MSFT-1", "closed", 10.0, Some(1000.0), "long"),
+                ("MSFT-2", "closed", 5.0, Some(250.0), "short"),
+                ("MSFT-2", "open", 5.0, Some(300.0), "short"),
+            ]
+        );
+        assert_eq!(
+            rows[2].end,
+            Utc.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(lots(&replay(), &HashMap::new(), now)[2].end, now);
+    }
+
+    #[test]
+    fn test_history() {
+        let replay = replay();
+        let cash: Vec<f64> = replay.steps.iter().map(Step::cash).collect();
+        assert_eq!(cash, vec![-1000.0, -1500.0, 5.5, -2.0, 3000.0]);
+        assert_eq!(replay.steps[1].position, 20.0);
+        assert_eq!(replay.steps[4].realized_gain, 1250.0);
+        assert_eq!(replay.steps[4].fields().len(), 9);
+    }
+}
@@ -0,0 +1,35 @@
+//!
+//! Fetch stock quotes from pluggable providers and summarize them with simple indicators.
+//!
+//! The `stock-tracker` binary is a thin command line wrapper around this library.
+//!
+//! ```
+//! use stock_tracker::indicators::{n_window_sma, price_diff};
+//!
+//! let closes = [10.0, 11.0, 12.5, 12.0];
+//! assert_eq!(price_diff(&closes), Some((2.0, 0.2)));
+//! assert_eq!(n_window_sma(2, &closes), Some(vec![10.5, 11.75, 12.25]));
+//! ```
+//!
+
+pub mod alert;
+pub mod align;
+pub mod backtest;
+pub mod benchmark;
+pub mod correlation;
+pub mod error;
+pub mod events;
+pub mod expr;
+pub mod fetch;
+pub mod indicators;
+pub mod ledger;
+pub mod model;
+pub mod notify;
+pub mod output;
+pub mod period;
+pub mod portfolio;
+pub mod provider;
+pub mod resample;
+pub mod risk;
+pub mod summary;
+pub mod watch;
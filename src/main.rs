@@ -1,124 +1,766 @@
 use chrono::prelude::*;
 use clap::Clap;
-use std::io::Result;
-use yahoo_finance_api as yahoo;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use stock_tracker::alert::sink::{create_sinks, AlertSink};
+use stock_tracker::alert::{Alert, AlertConfig, Alerts};
+use stock_tracker::backtest::strategy::Strategy;
+use stock_tracker::backtest::{Backtest, BacktestOptions, BacktestReport};
+use stock_tracker::correlation::{correlation_matrix, Measure};
+use stock_tracker::error::{Error, Result};
+use stock_tracker::events::EventRow;
+use stock_tracker::expr::Expr;
+use stock_tracker::fetch::{fetch_all, fetch_all_events, parse_symbols, FetchOptions};
+use stock_tracker::indicators::{Indicator, Window};
+use stock_tracker::ledger::report::LedgerReport;
+use stock_tracker::ledger::{self, Ledger, LotMethod};
+use stock_tracker::model::{Bar, BarInterval, Event, PriceField};
+use stock_tracker::output::{create_writer, OutputFormat, RecordWriter};
+use stock_tracker::period::{DateSpec, Period};
+use stock_tracker::portfolio::{valuation, Portfolio};
+use stock_tracker::provider::adjusted::AdjustedProvider;
+use stock_tracker::provider::cache::{CacheMode, CachedProvider};
+use stock_tracker::provider::{ProviderKind, ProviderResult, QuoteProvider};
+use stock_tracker::risk::RiskOptions;
+use stock_tracker::summary::{series_rows, Summary, SummaryOptions};
+use stock_tracker::watch::{parse_interval, Clock, SystemClock, Watcher};
 
-#[derive(Clap)]
-#[clap(version = "1.0", author = "sdaigo")]
+/// The process exit codes. Clap exits with 2 on invalid arguments.
+mod exit_code {
+    pub const SUCCESS: i32 = 0;
+    /// Nothing could be fetched because of an error not tied to a symbol
+    pub const FATAL: i32 = 1;
+    /// Some symbols failed, the others were printed
+    pub const PARTIAL_FAILURE: i32 = 3;
+    /// Every symbol failed
+    pub const ALL_FAILED: i32 = 4;
+}
 
+#[derive(Clap)]
+#[clap(
+    version = "1.0",
+    author = "sdaigo",
+    after_help = "Exits with 0 on success, 1 on fatal errors, 2 on invalid arguments, 3 if some symbols failed and 4 if all symbols failed."
+)]
 struct Opts {
     #[clap(short, long, default_value = "MSFT,GOOG,AAPL,UBER,IBM")]
     symbols: String,
+    /// The start of the period: a date (2024-01-01), an RFC3339 timestamp, a duration back from now (30d, 4w, 6mo, 1y) or a named period (today, ytd, qtd, mtd, last-month, last-quarter, last-year)
+    #[clap(short, long)]
+    from: DateSpec,
+    /// The end of the period in the same forms as --from, included. Defaults to now, or to the end of a named period given as --from
+    #[clap(short, long)]
+    to: Option<DateSpec>,
+    /// The length of a bar: 1m, 5m, 1h, 1d, 1wk or 1mo, resampled locally if the provider does not offer it. Defaults to the provider's own bars, daily for yahoo
+    #[clap(long)]
+    interval: Option<BarInterval>,
+    /// The quote provider to fetch history from
+    #[clap(short, long, default_value = "yahoo")]
+    provider: ProviderKind,
+    /// Recorded quotes for the csv provider: a directory of <SYMBOL>.csv files or a single file with a symbol column
     #[clap(short, long)]
-    from: String,
+    data: Option<PathBuf>,
+    /// Directory for the local quote cache
+    #[clap(long, default_value = ".stock-tracker-cache")]
+    cache_dir: PathBuf,
+    /// Always fetch from the provider and leave the cache untouched
+    #[clap(long)]
+    no_cache: bool,
+    /// Discard cached quotes and fetch the full period again
+    #[clap(long)]
+    refresh: bool,
+    /// Keep running and re-poll all symbols on this interval (e.g. 30s, 5m, 1h), printing only changed rows
+    #[clap(short, long, parse(try_from_str = parse_interval))]
+    watch: Option<Duration>,
+    /// The maximum number of symbols fetched at the same time
+    #[clap(long, default_value = "8")]
+    concurrency: usize,
+    /// Give up on a symbol if fetching it takes longer than this (e.g. 30s, 1m)
+    #[clap(long, default_value = "30s", parse(try_from_str = parse_interval))]
+    timeout: Duration,
+    /// The price summaries and indicators are computed from: close, adjclose, open, high, low or typical (the average of high, low and close)
+    #[clap(long, default_value = "adjclose")]
+    price_field: PriceField,
+    /// Replace the provider's adjusted closes with ones computed from the traded closes and the fetched dividends and splits
+    #[clap(long)]
+    recompute_adjclose: bool,
+    /// Comma separated simple moving average windows, each a number of bars (20) or a calendar duration (30d, 4w, 12h)
+    #[clap(long, default_value = "30", use_delimiter = true)]
+    sma: Vec<Window>,
+    /// Comma separated extra indicator columns as <name>:<parameters>, e.g. ema:20,wma:10,dema:20,tema:20,hma:9,rsi:14,macd:12:26:9,bb:20:2,tr,atr:14,stoch:14:3,vwap,obv,adv:20
+    #[clap(short, long, use_delimiter = true)]
+    indicators: Vec<Indicator>,
+    /// The annual risk-free rate in percent for the Sharpe and Sortino ratios
+    #[clap(long, default_value = "0")]
+    risk_free_rate: f64,
+    /// The confidence level of the value at risk in percent
+    #[clap(long, default_value = "95", parse(try_from_str = parse_confidence))]
+    var_confidence: f64,
+    /// Compare every symbol to this one, e.g. an index like SPY, adding beta, alpha, correlation, tracking error and relative performance
+    #[clap(short, long)]
+    benchmark: Option<String>,
+    /// Fetch dividends and splits, adding the dividends paid, the trailing twelve-month dividend yield and the total return with reinvested dividends
+    #[clap(long)]
+    dividends: bool,
+    /// Print every bar with its indicator values instead of one summary row per symbol
+    #[clap(long)]
+    series: bool,
+    /// One of csv, json, ndjson, table or parquet
+    #[clap(long, default_value = "csv")]
+    output_format: OutputFormat,
+    /// Write the output to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// Without a subcommand one summary row is printed per symbol.
+#[derive(Clap)]
+enum Command {
+    /// Print the correlation and covariance matrices of the returns of all symbols, aligned on their common timestamps
+    Correlation(CorrelationOpts),
+    /// Value the holdings of a portfolio file at the latest quotes, with profits, weights and returns per position and in total
+    Portfolio(PortfolioOpts),
+    /// Replay a ledger of transactions into tax lots and report positions, lots or the transaction history with realized and unrealized gains
+    Ledger(LedgerOpts),
+    /// List the dividends and splits of all symbols over the period
+    Events,
+    /// Simulate trading strategies on every symbol with commissions and slippage, reporting performance, the equity curve or the trades
+    Backtest(BacktestOpts),
+    /// Check the rules of an alerts file at the latest bar of every symbol and send the triggered alerts to its sinks, after every poll with --watch
+    Alerts(AlertsOpts),
+}
+
+#[derive(Clap)]
+struct CorrelationOpts {
+    /// Comma separated matrices to print: pearson, spearman or covariance
+    #[clap(
+        long,
+        default_value = "pearson,spearman,covariance",
+        use_delimiter = true
+    )]
+    measures: Vec<Measure>,
+}
+
+#[derive(Clap)]
+struct PortfolioOpts {
+    /// A .toml or .csv file listing the holdings with symbol, quantity, cost_basis and an optional purchase_date
+    file: PathBuf,
+}
+
+#[derive(Clap)]
+struct LedgerOpts {
+    /// A .csv file with the columns date, type (buy, sell, dividend, split or fee), symbol, quantity, price, amount and lot
+    file: PathBuf,
+    /// How sales are matched to lots: fifo, lifo, average or specific (every sale names its lot)
+    #[clap(long, default_value = "fifo")]
+    method: LotMethod,
+    /// One of positions, lots (a tax-lot report) or history
+    #[clap(long, default_value = "positions")]
+    report: LedgerReport,
+}
+
+#[derive(Clap)]
+struct BacktestOpts {
+    /// Comma separated strategies: buy-and-hold, sma:<fast>:<slow> (long while the fast average is above the slow one) or rsi:<period>:<lower>:<upper> (enter below lower, exit above upper). Defaults to buy-and-hold without --entry
+    #[clap(long, use_delimiter = true)]
+    strategy: Vec<Strategy>,
+    /// A condition to be long on, e.g. "sma(20) > sma(50) and rsi(14) < 70", backtested in addition to --strategy
+    #[clap(long)]
+    entry: Option<Expr>,
+    /// A condition to close the position on, which is then held from a bar where --entry holds until one where this does
+    #[clap(long)]
+    exit: Option<Expr>,
+    /// The cash the account starts with
+    #[clap(long, default_value = "10000")]
+    capital: f64,
+    /// The fixed cost of every fill
+    #[clap(long, default_value = "0")]
+    commission: f64,
+    /// How much worse than the open every fill is, in percent
+    #[clap(long, default_value = "0")]
+    slippage: f64,
+    /// One of summary, equity or trades
+    #[clap(long, default_value = "summary")]
+    report: BacktestReport,
+}
+
+#[derive(Clap)]
+struct AlertsOpts {
+    /// A .toml file with an [[alerts]] table per rule (type cross_above, cross_below, change, new_high, new_low, sma_cross or expr) and optional [[sinks]] (type stdout, file, webhook, smtp or exec). Rules without a symbol apply to --symbols
+    file: PathBuf,
+}
+
+fn parse_confidence(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(confidence) if confidence > 0.0 && confidence < 100.0 => Ok(confidence),
+        _ => Err(format!(
+            "invalid confidence level '{}', expected a percentage between 0 and 100",
+            s
+        )),
+    }
+}
+
+fn main() {
+    let opts = Opts::parse();
+
+    let code = match run(opts) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit_code::FATAL
+        }
+    };
+    std::process::exit(code);
 }
 
 ///
-/// Calculates the absolute / relative price change between the beginning and ending of f64 series.
-///
-/// # Returns
-/// A tuple `(absolute, relative)` diff.
+/// Prints the failed symbols with their causes and picks the matching exit code.
 ///
-fn price_diff(a: &[f64]) -> Option<(f64, f64)> {
-    if !a.is_empty() {
-        let (first, last) = (a.first().unwrap(), a.last().unwrap());
-        let diff = last - first;
+fn report_failures(failures: &[(String, Error)], total: usize) -> i32 {
+    if failures.is_empty() {
+        return exit_code::SUCCESS;
+    }
 
-        let first = if *first == 0.0 { 1.0 } else { *first };
-        let rel_diff = diff / first;
+    eprintln!("Failed to fetch {} of {} symbols:", failures.len(), total);
+    for (symbol, e) in failures {
+        eprintln!("  {}: {}", symbol, e);
+    }
 
-        Some((diff, rel_diff))
+    if failures.len() == total {
+        exit_code::ALL_FAILED
     } else {
-        None
+        exit_code::PARTIAL_FAILURE
     }
 }
 
 ///
-/// Calculate a simple moving average over the entire series.
+/// Treats an empty history as an error, as nothing can be summarized from it.
 ///
-fn n_window_sma(n: usize, series: &[f64]) -> Option<Vec<f64>> {
-    if !series.is_empty() && n > 1 {
-        Some(
-            series
-                .windows(n)
-                .map(|w| w.iter().sum::<f64>() / w.len() as f64)
-                .collect(),
-        )
-    } else {
-        None
-    }
+fn non_empty(symbol: &str, bars: Result<Vec<Bar>>) -> Result<Vec<Bar>> {
+    bars.and_then(|bars| {
+        if bars.is_empty() {
+            Err(Error::EmptyRange(symbol.to_string()))
+        } else {
+            Ok(bars)
+        }
+    })
 }
 
 ///
-/// Find the max value in a series of f64
+/// Fetches all `symbols` concurrently with the limits from the command line.
 ///
-fn max(series: &[f64]) -> Option<f64> {
-    if series.is_empty() {
-        None
-    } else {
-        Some(series.iter().fold(f64::MIN, |acc, q| acc.max(*q)))
+fn fetch(
+    opts: &Opts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+) -> Result<Vec<(String, ProviderResult<Vec<Bar>>)>> {
+    let options = FetchOptions {
+        concurrency: opts.concurrency,
+        timeout: Some(opts.timeout),
+        interval: opts.interval,
+    };
+    let runtime = tokio::runtime::Runtime::new()?;
+    Ok(runtime.block_on(fetch_all(
+        provider,
+        symbols,
+        period.from,
+        period.to,
+        options,
+    )))
+}
+
+///
+/// Fetches the corporate actions of all `symbols` over `period`.
+///
+fn fetch_events(
+    opts: &Opts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+) -> Result<Vec<(String, ProviderResult<Vec<Event>>)>> {
+    let options = FetchOptions {
+        concurrency: opts.concurrency,
+        timeout: Some(opts.timeout),
+        interval: opts.interval,
+    };
+    let runtime = tokio::runtime::Runtime::new()?;
+    Ok(runtime.block_on(fetch_all_events(
+        provider,
+        symbols,
+        period.from,
+        period.to,
+        options,
+    )))
+}
+
+fn run_correlation(
+    opts: &Opts,
+    correlation: &CorrelationOpts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    let mut histories = Vec::new();
+    let mut failures = Vec::new();
+    for (symbol, bars) in fetch(opts, provider, symbols, period)? {
+        match non_empty(&symbol, bars) {
+            Ok(bars) => histories.push((symbol, bars)),
+            Err(e) => failures.push((symbol, e)),
+        }
+    }
+
+    let (rows, observations) = correlation_matrix(&histories, &correlation.measures);
+    if observations < 2 && !histories.is_empty() {
+        eprintln!(
+            "Only {} common returns across all symbols, the matrix is left empty",
+            observations
+        );
+    }
+    for row in rows {
+        writer.write(&row)?;
+    }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
+}
+
+fn run_portfolio(
+    opts: &Opts,
+    portfolio: &PortfolioOpts,
+    provider: Arc<dyn QuoteProvider>,
+    period: Period,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    let portfolio = Portfolio::load(&portfolio.file)?;
+    let symbols = portfolio.symbols();
+
+    let mut history = HashMap::new();
+    let mut failures = Vec::new();
+    for (symbol, bars) in fetch(opts, provider, &symbols, period)? {
+        match non_empty(&symbol, bars) {
+            Ok(bars) => {
+                history.insert(symbol, bars);
+            }
+            Err(e) => failures.push((symbol, e)),
+        }
+    }
+
+    for row in valuation(&portfolio, &history, period.from) {
+        writer.write(&row)?;
     }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
+}
+
+fn run_ledger(
+    opts: &Opts,
+    ledger_opts: &LedgerOpts,
+    provider: Arc<dyn QuoteProvider>,
+    period: Period,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    let ledger = Ledger::load(&ledger_opts.file)?;
+    let replay = ledger.replay(ledger_opts.method)?;
+    let symbols = ledger.symbols();
+
+    let mut failures = Vec::new();
+    let mut history = HashMap::new();
+    if ledger_opts.report != LedgerReport::History {
+        for (symbol, bars) in fetch(opts, provider, &symbols, period)? {
+            match non_empty(&symbol, bars) {
+                Ok(bars) => {
+                    history.insert(symbol, bars);
+                }
+                Err(e) => failures.push((symbol, e)),
+            }
+        }
+    }
+
+    match ledger_opts.report {
+        LedgerReport::Positions => {
+            for row in ledger::report::positions(&replay, &symbols, &history) {
+                writer.write(&row)?;
+            }
+        }
+        LedgerReport::Lots => {
+            for row in ledger::report::lots(&replay, &history, period.to) {
+                writer.write(&row)?;
+            }
+        }
+        LedgerReport::History => {
+            for step in &replay.steps {
+                writer.write(step)?;
+            }
+        }
+    }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
+}
+
+fn run_events(
+    opts: &Opts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    let mut failures = Vec::new();
+    for (symbol, events) in fetch_events(opts, provider, symbols, period)? {
+        match events {
+            Ok(events) => {
+                for event in events {
+                    writer.write(&EventRow {
+                        symbol: symbol.clone(),
+                        event,
+                    })?;
+                }
+            }
+            Err(e) => failures.push((symbol, e)),
+        }
+    }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
+}
+
+fn run_backtest(
+    opts: &Opts,
+    backtest: &BacktestOpts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+    summary_options: &SummaryOptions,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    if backtest.capital <= 0.0 || backtest.commission < 0.0 || backtest.slippage < 0.0 {
+        return Err(Error::Config(
+            "--capital has to be positive, --commission and --slippage must not be negative"
+                .to_string(),
+        ));
+    }
+    let mut strategies = backtest.strategy.clone();
+    match (&backtest.entry, &backtest.exit) {
+        (Some(entry), exit) => strategies.push(Strategy::Rule {
+            entry: entry.clone(),
+            exit: exit.clone(),
+        }),
+        (None, Some(_)) => {
+            return Err(Error::Config("--exit requires --entry".to_string()));
+        }
+        (None, None) if strategies.is_empty() => strategies.push(Strategy::BuyAndHold),
+        (None, None) => {}
+    }
+    let options = BacktestOptions {
+        capital: backtest.capital,
+        commission: backtest.commission,
+        slippage: backtest.slippage / 100.0,
+        risk: summary_options.risk,
+    };
+
+    let mut failures = Vec::new();
+    for (symbol, bars) in fetch(opts, provider, symbols, period)? {
+        let bars = match non_empty(&symbol, bars) {
+            Ok(bars) => bars,
+            Err(e) => {
+                failures.push((symbol, e));
+                continue;
+            }
+        };
+        let bars: Vec<Bar> = bars
+            .iter()
+            .map(|b| b.priced_by(summary_options.price_field))
+            .collect();
+        for strategy in &strategies {
+            let result = match Backtest::run(&symbol, &bars, strategy, &options) {
+                Some(result) => result,
+                None => continue,
+            };
+            match backtest.report {
+                BacktestReport::Summary => writer.write(&result.summary)?,
+                BacktestReport::Equity => {
+                    for point in &result.equity {
+                        writer.write(point)?;
+                    }
+                }
+                BacktestReport::Trades => {
+                    for trade in &result.trades {
+                        writer.write(trade)?;
+                    }
+                }
+            }
+        }
+    }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
 }
 
 ///
-/// Find the min value in a series of f64
+/// Sends `alert` to every sink.
 ///
-fn min(series: &[f64]) -> Option<f64> {
-    if series.is_empty() {
-        None
-    } else {
-        Some(series.iter().fold(f64::MAX, |acc, q| acc.min(*q)))
+fn send_alert(sinks: &mut [Box<dyn AlertSink + '_>], alert: &Alert) -> Result<()> {
+    for sink in sinks.iter_mut() {
+        sink.send(alert)?;
     }
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let opts = Opts::parse();
+fn run_alerts(
+    opts: &Opts,
+    alerts: &AlertsOpts,
+    provider: Arc<dyn QuoteProvider>,
+    symbols: &[String],
+    period: Period,
+    summary_options: SummaryOptions,
+    writer: &mut dyn RecordWriter,
+) -> Result<i32> {
+    let config = AlertConfig::load(&alerts.file)?;
+    let symbols = config.symbols(symbols);
+    let price_field = summary_options.price_field;
+    let priced =
+        |bars: &[Bar]| -> Vec<Bar> { bars.iter().map(|b| b.priced_by(price_field)).collect() };
+    let mut sinks = create_sinks(&config.sinks, writer, opts.timeout)?;
+    // every bar of the period sees the history before it as well, e.g. for a 52-week high or
+    // the warm-up of sma(200), with a week to spare for holidays
+    let lookback =
+        config.lookback(opts.interval.unwrap_or(BarInterval::Day)) + chrono::Duration::weeks(1);
+    let history = Period {
+        from: period.from - lookback,
+        ..period
+    };
+    let mut rules = Alerts::new(config.rules).with_period_start(period.from);
 
-    let provider = yahoo::YahooConnector::new();
-
-    let symbols = opts.symbols.split(',');
-    let from: DateTime<Utc> = opts.from.parse().expect("Failed to parse 'from' date");
-
-    // print headers
-    println!("period start,symbol,price,change %,min,max,30d avg");
-
-    for symbol in symbols {
-        if let Ok(response) = provider.get_quote_history(symbol, from, Utc::now()) {
-            match response.quotes() {
-                Ok(mut quotes) => {
-                    if !quotes.is_empty() {
-                        quotes.sort_by_cached_key(|k| k.timestamp);
-                        let closes: Vec<f64> = quotes.iter().map(|q| q.adjclose as f64).collect();
-
-                        if !closes.is_empty() {
-                            let max_period: f64 = max(&closes).unwrap();
-                            let min_period: f64 = min(&closes).unwrap();
-
-                            let last_price = *closes.last().unwrap_or(&0.0);
-                            let (_, pct_change) = price_diff(&closes).unwrap_or((0.0, 0.0));
-                            let sma = n_window_sma(30, &closes).unwrap_or_default();
-
-                            println!(
-                                "{},{},{},{}%,${},${},${}",
-                                from.to_rfc3339(),
-                                symbol,
-                                last_price,
-                                pct_change * 100.0,
-                                min_period,
-                                max_period,
-                                sma.last().unwrap_or(&0.0)
-                            )
-                        }
+    if let Some(interval) = opts.watch {
+        if !opts.output_format.is_streaming() {
+            return Err(Error::Config(
+                "--watch requires a streaming output format (csv or ndjson)".to_string(),
+            ));
+        }
+        let mut watcher = Watcher::new(&*provider, symbols, history.from, summary_options);
+        if let Some(interval) = opts.interval {
+            watcher = watcher.with_interval(interval);
+        }
+        loop {
+            for (symbol, update) in watcher.poll(SystemClock.now()) {
+                let sent = update.and_then(|_| {
+                    for alert in rules.check(&symbol, &priced(watcher.bars(&symbol))) {
+                        send_alert(&mut sinks, &alert)?;
                     }
+                    Ok(())
+                });
+                if let Err(e) = sent {
+                    eprintln!("{}: {}", symbol, e);
                 }
-                _ => {
-                    eprint!("No quotes found '{}'", symbol);
+            }
+            SystemClock.sleep(interval);
+        }
+    }
+
+    let mut failures = Vec::new();
+    for (symbol, bars) in fetch(opts, provider, &symbols, history)? {
+        match non_empty(&symbol, bars) {
+            Ok(bars) => {
+                for alert in rules.check(&symbol, &priced(&bars)) {
+                    send_alert(&mut sinks, &alert)?;
                 }
             }
+            Err(e) => failures.push((symbol, e)),
+        }
+    }
+    for sink in sinks.iter_mut() {
+        sink.finish()?;
+    }
+
+    Ok(report_failures(&failures, symbols.len()))
+}
+
+fn run(opts: Opts) -> Result<i32> {
+    let alerts = matches!(opts.command, Some(Command::Alerts(_)));
+    if opts.command.is_some() && (opts.series || opts.dividends) {
+        return Err(Error::Config(
+            "--series and --dividends only apply to summaries, not to subcommands".to_string(),
+        ));
+    }
+    if opts.watch.is_some() && opts.command.is_some() && !alerts {
+        return Err(Error::Config(
+            "--watch only applies to summaries and alerts".to_string(),
+        ));
+    }
+    if opts.series && (opts.benchmark.is_some() || opts.dividends) {
+        return Err(Error::Config(
+            "--benchmark and --dividends only apply to summaries, not to --series".to_string(),
+        ));
+    }
+    if opts.watch.is_some() && (opts.dividends || opts.to.is_some()) {
+        return Err(Error::Config(
+            "--dividends and --to are not supported with --watch".to_string(),
+        ));
+    }
+    let period = Period::resolve(&opts.from, opts.to.as_ref(), Utc::now())?;
+
+    let mut provider = opts.provider.build(opts.data.as_deref())?;
+    if opts.provider.is_remote() && !opts.no_cache {
+        let mode = if opts.refresh {
+            CacheMode::Refresh
         } else {
-            eprint!("No quotes found '{}'", symbol);
+            CacheMode::Incremental
+        };
+        let dir = opts.cache_dir.join(opts.provider.to_string());
+        provider = Box::new(CachedProvider::new(provider, dir, mode));
+    }
+
+    if opts.recompute_adjclose {
+        provider = Box::new(AdjustedProvider::new(provider));
+    }
+
+    let provider: Arc<dyn QuoteProvider> = Arc::from(provider);
+
+    let symbols = parse_symbols(&opts.symbols);
+    let summary_options = SummaryOptions {
+        indicators: opts
+            .sma
+            .iter()
+            .map(|window| Indicator::Sma(*window))
+            .chain(opts.indicators.iter().copied())
+            .collect(),
+        price_field: opts.price_field,
+        risk: RiskOptions {
+            risk_free_rate: opts.risk_free_rate / 100.0,
+            confidence: opts.var_confidence / 100.0,
+            periods_per_year: opts.interval.unwrap_or(BarInterval::Day).periods_per_year(),
+        },
+    };
+
+    let out: Box<dyn std::io::Write + Send> = match &opts.output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = create_writer(opts.output_format, out)?;
+
+    match &opts.command {
+        Some(Command::Correlation(correlation)) => {
+            return run_correlation(
+                &opts,
+                correlation,
+                provider,
+                &symbols,
+                period,
+                writer.as_mut(),
+            )
+        }
+        Some(Command::Portfolio(portfolio)) => {
+            return run_portfolio(&opts, portfolio, provider, period, writer.as_mut())
+        }
+        Some(Command::Ledger(ledger)) => {
+            return run_ledger(&opts, ledger, provider, period, writer.as_mut())
+        }
+        Some(Command::Events) => {
+            return run_events(&opts, provider, &symbols, period, writer.as_mut())
         }
+        Some(Command::Backtest(backtest)) => {
+            return run_backtest(
+                &opts,
+                backtest,
+                provider,
+                &symbols,
+                period,
+                &summary_options,
+                writer.as_mut(),
+            )
+        }
+        Some(Command::Alerts(alerts)) => {
+            return run_alerts(
+                &opts,
+                alerts,
+                provider,
+                &symbols,
+                period,
+                summary_options,
+                writer.as_mut(),
+            )
+        }
+        None => {}
     }
 
-    Ok(())
+    if let Some(interval) = opts.watch {
+        if !opts.output_format.is_streaming() {
+            return Err(Error::Config(
+                "--watch requires a streaming output format (csv or ndjson)".to_string(),
+            ));
+        }
+        let mut watcher = Watcher::new(&*provider, symbols, period.from, summary_options);
+        if let Some(interval) = opts.interval {
+            watcher = watcher.with_interval(interval);
+        }
+        if let Some(benchmark) = opts.benchmark {
+            watcher = watcher.with_benchmark(benchmark);
+        }
+        watcher.run(&SystemClock, interval, None, |symbol, update| {
+            if let Err(e) = update.and_then(|summary| writer.write(&summary)) {
+                eprintln!("{}: {}", symbol, e);
+            }
+        });
+        return Ok(exit_code::SUCCESS);
+    }
+
+    // the benchmark is fetched last, even if it is also in the watchlist
+    let fetched: Vec<String> = symbols.iter().chain(&opts.benchmark).cloned().collect();
+    let mut results = fetch(&opts, provider.clone(), &fetched, period)?;
+    let benchmark = match &opts.benchmark {
+        Some(benchmark) => {
+            let (_, bars) = results.pop().expect("one result per fetched symbol");
+            Some(non_empty(benchmark, bars)?)
+        }
+        None => None,
+    };
+
+    // the trailing dividend yield needs the events of the last year, even for shorter periods
+    let mut events = HashMap::new();
+    if opts.dividends {
+        let year = Period {
+            from: period.from.min(period.to - chrono::Duration::days(365)),
+            ..period
+        };
+        events = fetch_events(&opts, provider, &symbols, year)?
+            .into_iter()
+            .collect();
+    }
+
+    let mut failures = Vec::new();
+    for (symbol, bars) in results {
+        let bars = non_empty(&symbol, bars).and_then(|bars| match events.remove(&symbol) {
+            Some(events) => Ok((bars, Some(events?))),
+            None => Ok((bars, None)),
+        });
+        match bars {
+            Ok((bars, _)) if opts.series => {
+                for row in series_rows(&symbol, &bars, &summary_options) {
+                    writer.write(&row)?;
+                }
+            }
+            Ok((bars, events)) => {
+                if let Some(mut summary) = Summary::from_bars(&symbol, &bars, &summary_options) {
+                    if let Some(benchmark) = &benchmark {
+                        summary.compare_to(&bars, benchmark, &summary_options);
+                    }
+                    if let Some(events) = &events {
+                        summary.add_events(&bars, events);
+                    }
+                    writer.write(&summary)?;
+                }
+            }
+            Err(e) => failures.push((symbol, e)),
+        }
+    }
+    writer.finish()?;
+
+    Ok(report_failures(&failures, symbols.len()))
 }
@@ -0,0 +1,219 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// A single normalized OHLCV bar, independent of the provider it was fetched from.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub adjclose: f64,
+    pub volume: u64,
+}
+
+impl Bar {
+    ///
+    /// The factor between the adjusted and the traded close, 1 if the close is zero.
+    ///
+    pub fn adjustment_factor(&self) -> f64 {
+        if self.close == 0.0 {
+            1.0
+        } else {
+            self.adjclose / self.close
+        }
+    }
+
+    ///
+    /// Returns the bar with open, high, low and close scaled like the adjusted close, so that
+    /// intraday ranges are comparable across splits and dividends.
+    ///
+    pub fn adjusted(&self) -> Bar {
+        let factor = self.adjustment_factor();
+        Bar {
+            open: self.open * factor,
+            high: self.high * factor,
+            low: self.low * factor,
+            close: self.adjclose,
+            ..self.clone()
+        }
+    }
+
+    ///
+    /// The price of the bar selected by `field`.
+    ///
+    pub fn price(&self, field: PriceField) -> f64 {
+        match field {
+            PriceField::Close => self.close,
+            PriceField::Adjclose => self.adjclose,
+            PriceField::Open => self.open,
+            PriceField::High => self.high,
+            PriceField::Low => self.low,
+            PriceField::Typical => (self.high + self.low + self.close) / 3.0,
+        }
+    }
+
+    ///
+    /// Returns the bar with both closes replaced by the price selected by `field`, so that
+    /// everything reading the adjusted close uses that price instead. Open, high and low stay
+    /// as traded, except for `PriceField::Adjclose`, which returns the bar unchanged.
+    ///
+    pub fn priced_by(&self, field: PriceField) -> Bar {
+        if field == PriceField::Adjclose {
+            return self.clone();
+        }
+        let price = self.price(field);
+        Bar {
+            close: price,
+            adjclose: price,
+            ..self.clone()
+        }
+    }
+}
+
+///
+/// The price of a bar that summaries and indicators are computed from.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceField {
+    /// The traded close
+    Close,
+    /// The close adjusted for splits and dividends
+    Adjclose,
+    Open,
+    High,
+    Low,
+    /// The average of high, low and close
+    Typical,
+}
+
+impl fmt::Display for PriceField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceField::Close => write!(f, "close"),
+            PriceField::Adjclose => write!(f, "adjclose"),
+            PriceField::Open => write!(f, "open"),
+            PriceField::High => write!(f, "high"),
+            PriceField::Low => write!(f, "low"),
+            PriceField::Typical => write!(f, "typical"),
+        }
+    }
+}
+
+impl FromStr for PriceField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "close" => Ok(PriceField::Close),
+            "adjclose" => Ok(PriceField::Adjclose),
+            "open" => Ok(PriceField::Open),
+            "high" => Ok(PriceField::High),
+            "low" => Ok(PriceField::Low),
+            "typical" => Ok(PriceField::Typical),
+            _ => Err(format!(
+                "unknown price field '{}', expected close, adjclose, open, high, low or typical",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// The time span a single bar covers.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BarInterval {
+    Minute,
+    FiveMinutes,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl BarInterval {
+    ///
+    /// Whether bars of this interval are shorter than a day.
+    ///
+    pub fn is_intraday(&self) -> bool {
+        *self < BarInterval::Day
+    }
+
+    ///
+    /// The number of bars of this interval in a year of 252 trading sessions of 6.5 hours.
+    ///
+    pub fn periods_per_year(&self) -> f64 {
+        match self {
+            BarInterval::Minute => 252.0 * 390.0,
+            BarInterval::FiveMinutes => 252.0 * 78.0,
+            // the last hourly bar of a session is only half an hour long
+            BarInterval::Hour => 252.0 * 7.0,
+            BarInterval::Day => 252.0,
+            BarInterval::Week => 52.0,
+            BarInterval::Month => 12.0,
+        }
+    }
+
+    ///
+    /// The calendar time from one bar of this interval to the next on average, counting nights,
+    /// weekends and holidays.
+    ///
+    pub fn average_span(&self) -> Duration {
+        Duration::seconds((365.25 * 86_400.0 / self.periods_per_year()) as i64)
+    }
+}
+
+impl fmt::Display for BarInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarInterval::Minute => write!(f, "1m"),
+            BarInterval::FiveMinutes => write!(f, "5m"),
+            BarInterval::Hour => write!(f, "1h"),
+            BarInterval::Day => write!(f, "1d"),
+            BarInterval::Week => write!(f, "1wk"),
+            BarInterval::Month => write!(f, "1mo"),
+        }
+    }
+}
+
+impl FromStr for BarInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "1m" => Ok(BarInterval::Minute),
+            "5m" => Ok(BarInterval::FiveMinutes),
+            "1h" => Ok(BarInterval::Hour),
+            "1d" => Ok(BarInterval::Day),
+            "1wk" => Ok(BarInterval::Week),
+            "1mo" => Ok(BarInterval::Month),
+            _ => Err(format!(
+                "unknown interval '{}', expected 1m, 5m, 1h, 1d, 1wk or 1mo",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// A corporate action of a symbol.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub timestamp: DateTime<Utc>,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    /// A cash dividend per share, dated on the ex-dividend date
+    Dividend(f64),
+    /// A split, with the number of new shares per old share, e.g. 0.1 for a 1:10 reverse split
+    Split(f64),
+}
@@ -0,0 +1,26 @@
+//!
+//! Notifiers deliver short messages, e.g. triggered alerts, to people: by webhook to chat
+//! tools, by email or through a local command.
+//!
+
+use crate::error::Result;
+
+pub mod exec;
+pub mod smtp;
+pub mod webhook;
+
+///
+/// A message for people, with a one-line subject and a plain text body.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Notifier {
+    ///
+    /// Delivers `notification`, returning once it was accepted by the receiving end.
+    ///
+    fn notify(&self, notification: &Notification) -> Result<()>;
+}
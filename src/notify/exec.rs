@@ -0,0 +1,115 @@
+//!
+//! Hands notifications to a local command, e.g. `notify-send` for desktop notifications or a
+//! script that pages someone.
+//!
+
+use super::{Notification, Notifier};
+use crate::error::{Error, Result};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+///
+/// Runs a command for every notification. `{subject}` and `{body}` in its arguments are
+/// replaced, and the command also gets the subject and body in the environment variables
+/// `STOCK_TRACKER_SUBJECT` and `STOCK_TRACKER_BODY` and the body on stdin.
+///
+pub struct ExecNotifier {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExecNotifier {
+    ///
+    /// # Errors
+    /// `Error::Config` if `command` is empty.
+    ///
+    pub fn new(command: &[String]) -> Result<Self> {
+        match command.split_first() {
+            Some((program, args)) if !program.trim().is_empty() => Ok(ExecNotifier {
+                program: program.clone(),
+                args: args.to_vec(),
+            }),
+            _ => Err(Error::Config(
+                "the command of an exec notifier is empty".to_string(),
+            )),
+        }
+    }
+}
+
+impl Notifier for ExecNotifier {
+    ///
+    /// # Errors
+    /// `Error::Io` if the command cannot be started or exits unsuccessfully.
+    ///
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let args = self.args.iter().map(|arg| {
+            arg.replace("{subject}", &notification.subject)
+                .replace("{body}", &notification.body)
+        });
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .env("STOCK_TRACKER_SUBJECT", &notification.subject)
+            .env("STOCK_TRACKER_BODY", &notification.body)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // the command does not have to read its input
+            match stdin.write_all(notification.body.as_bytes()) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(Error::Io(io::Error::other(format!(
+                "'{}' exited with {}",
+                self.program, status
+            ))));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exec_notifier() {
+        let path =
+            std::env::temp_dir().join(format!("stock-tracker-exec-{}.txt", std::process::id()));
+        let script = format!(
+            "printf '%s|%s|' \"$1\" \"$STOCK_TRACKER_SUBJECT\" > {}; cat >> {}",
+            path.display(),
+            path.display()
+        );
+        let notifier =
+            ExecNotifier::new(&command(&["sh", "-c", &script, "sh", "[{subject}]"])).unwrap();
+        notifier
+            .notify(&Notification {
+                subject: "MSFT: breakout".to_string(),
+                body: "crossed 250".to_string(),
+            })
+            .unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "[MSFT: breakout]|MSFT: breakout|crossed 250");
+    }
+
+    #[test]
+    fn test_exec_failures() {
+        let notification = Notification {
+            subject: String::new(),
+            body: String::new(),
+        };
+        assert!(matches!(ExecNotifier::new(&[]), Err(Error::Config(_))));
+        let failing = ExecNotifier::new(&command(&["sh", "-c", "exit 3"])).unwrap();
+        assert!(matches!(failing.notify(&notification), Err(Error::Io(_))));
+        let missing = ExecNotifier::new(&command(&["stock-tracker-no-such-command"])).unwrap();
+        assert!(matches!(missing.notify(&notification), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,304 @@
+//!
+//! Sends notifications as plain text emails over SMTP.
+//!
+
+use super::{Notification, Notifier};
+use crate::error::{Error, Result};
+use chrono::prelude::*;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+///
+/// Delivers emails to an SMTP server without TLS or authentication, such as a local relay.
+///
+pub struct SmtpNotifier {
+    /// `host:port`
+    server: String,
+    from: String,
+    to: Vec<String>,
+    timeout: Duration,
+}
+
+impl SmtpNotifier {
+    ///
+    /// # Errors
+    /// `Error::Config` without recipients or if an address is not of the form `user@domain`.
+    ///
+    pub fn new(server: &str, from: &str, to: &[String], timeout: Duration) -> Result<Self> {
+        if to.is_empty() {
+            return Err(Error::Config(
+                "an email needs at least one recipient".to_string(),
+            ));
+        }
+        for address in std::iter::once(from).chain(to.iter().map(String::as_str)) {
+            let valid = matches!(address.split_once('@'), Some((user, domain)) if !user.is_empty() && !domain.is_empty())
+                && !address.contains(|c: char| c.is_whitespace() || "<>".contains(c));
+            if !valid {
+                return Err(Error::Config(format!(
+                    "invalid email address '{}'",
+                    address
+                )));
+            }
+        }
+        Ok(SmtpNotifier {
+            server: server.to_string(),
+            from: from.to_string(),
+            to: to.to_vec(),
+            timeout,
+        })
+    }
+
+    ///
+    /// The message with headers, CRLF line endings and the lines starting with a dot escaped.
+    ///
+    fn message(&self, notification: &Notification, date: DateTime<Utc>) -> String {
+        let mut message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            self.to
+                .iter()
+                .map(|to| format!("<{}>", to))
+                .collect::<Vec<_>>()
+                .join(", "),
+            encode_header(&notification.subject),
+            date.to_rfc2822()
+        );
+        for line in notification.body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+///
+/// Encodes a header value as an RFC 2047 encoded word if it is not plain ASCII.
+///
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.is_ascii() {
+        return value;
+    }
+    let encoded: String = value
+        .bytes()
+        .map(|b| match b {
+            b' ' => "_".to_string(),
+            b if b.is_ascii_alphanumeric() => (b as char).to_string(),
+            b => format!("={:02X}", b),
+        })
+        .collect();
+    format!("=?utf-8?Q?{}?=", encoded)
+}
+
+///
+/// One SMTP session on a connected stream.
+///
+struct Session {
+    reader: BufReader<TcpStream>,
+    server: String,
+}
+
+impl Session {
+    ///
+    /// Reads a possibly multi-line reply and checks its code.
+    ///
+    fn expect(&mut self, code: &str) -> Result<()> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(self.error("the connection was closed"));
+            }
+            reply.push_str(line.trim_end());
+            // the last line of a reply has a space after the code instead of a dash
+            if line.len() < 4 || line.as_bytes()[3] != b'-' {
+                break;
+            }
+            reply.push(' ');
+        }
+        if reply.starts_with(code) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("unexpected reply '{}'", reply)))
+        }
+    }
+
+    fn send(&mut self, command: &str, code: &str) -> Result<()> {
+        let stream = self.reader.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        self.expect(code)
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::Network(format!("smtp {}: {}", self.server, message))
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let address = self
+            .server
+            .to_socket_addrs()
+            .map_err(|e| Error::Network(format!("smtp {}: {}", self.server, e)))?
+            .next()
+            .ok_or_else(|| Error::Network(format!("smtp {}: no address", self.server)))?;
+        let stream = TcpStream::connect_timeout(&address, self.timeout)
+            .map_err(|e| Error::Network(format!("smtp {}: {}", self.server, e)))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut session = Session {
+            reader: BufReader::new(stream),
+            server: self.server.clone(),
+        };
+
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+        session.expect("220")?;
+        session.send(&format!("EHLO {}", domain), "250")?;
+        session.send(&format!("MAIL FROM:<{}>", self.from), "250")?;
+        for to in &self.to {
+            session.send(&format!("RCPT TO:<{}>", to), "25")?;
+        }
+        session.send("DATA", "354")?;
+        let message = self.message(notification, Utc::now());
+        session.send(&format!("{}.", message), "250")?;
+        // the message was accepted, a failing goodbye does not matter
+        session.send("QUIT", "221").ok();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    ///
+    /// Accepts one SMTP session, answers every command with the reply of `replies` for its
+    /// verb and returns the commands and the message data it received.
+    ///
+    fn serve_once(
+        replies: &'static [(&'static str, &'static str)],
+    ) -> (String, std::thread::JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut commands = Vec::new();
+            let mut data = String::new();
+            reader
+                .get_mut()
+                .write_all(b"220 stand-in ready\r\n")
+                .unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let command = line.trim_end().to_string();
+                let verb = command.split([' ', ':']).next().unwrap().to_string();
+                commands.push(command);
+                let default = match verb.as_str() {
+                    "DATA" => "354 end with a dot",
+                    "QUIT" => "221 bye",
+                    _ => "250 ok",
+                };
+                let reply = replies
+                    .iter()
+                    .find(|(v, _)| *v == verb)
+                    .map_or(default, |(_, reply)| reply);
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+                reader.get_mut().write_all(b"\r\n").unwrap();
+                if verb == "DATA" && reply.starts_with("354") {
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&line);
+                    }
+                    reader.get_mut().write_all(b"250 queued\r\n").unwrap();
+                }
+                if verb == "QUIT" || !reply.starts_with(['2', '3']) {
+                    break;
+                }
+            }
+            (commands, data)
+        });
+        (server, handle)
+    }
+
+    fn notifier(server: &str) -> SmtpNotifier {
+        SmtpNotifier::new(
+            server,
+            "tracker@example.com",
+            &["ops@example.com".to_string(), "me@example.com".to_string()],
+            Duration::from_secs(5),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_smtp_notifier() {
+        let (server, stand_in) = serve_once(&[("EHLO", "250-stand-in\r\n250 8BITMIME")]);
+        notifier(&server)
+            .notify(&Notification {
+                subject: "MSFT: breakout".to_string(),
+                body: "crossed 250\n.hidden".to_string(),
+            })
+            .unwrap();
+
+        let (commands, data) = stand_in.join().unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                "EHLO example.com",
+                "MAIL FROM:<tracker@example.com>",
+                "RCPT TO:<ops@example.com>",
+                "RCPT TO:<me@example.com>",
+                "DATA",
+                "QUIT"
+            ]
+        );
+        assert!(data.starts_with("From: <tracker@example.com>\r\nTo: <ops@example.com>, <me@example.com>\r\nSubject: MSFT: breakout\r\n"));
+        assert!(data.ends_with("\r\n\r\ncrossed 250\r\n..hidden\r\n"));
+    }
+
+    #[test]
+    fn test_smtp_rejected_recipient() {
+        let (server, stand_in) = serve_once(&[("RCPT", "550 no such user")]);
+        let result = notifier(&server).notify(&Notification {
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        });
+        match result {
+            Err(Error::Network(message)) => assert!(message.ends_with("'550 no such user'")),
+            other => panic!("unexpected {:?}", other),
+        }
+        stand_in.join().unwrap();
+    }
+
+    #[test]
+    fn test_new_and_encode_header() {
+        let to = ["ops@example.com".to_string()];
+        let new = |from: &str, to: &[String]| {
+            SmtpNotifier::new("localhost:25", from, to, Duration::from_secs(1))
+        };
+        assert!(new("tracker@example.com", &to).is_ok());
+        assert!(matches!(new("tracker", &to), Err(Error::Config(_))));
+        assert!(matches!(new("a b@example.com", &to), Err(Error::Config(_))));
+        assert!(matches!(
+            new("tracker@example.com", &[]),
+            Err(Error::Config(_))
+        ));
+
+        assert_eq!(encode_header("MSFT up\r\n"), "MSFT up  ");
+        assert_eq!(encode_header("7203 ↑"), "=?utf-8?Q?7203_=E2=86=91?=");
+    }
+}
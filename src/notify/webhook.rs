@@ -0,0 +1,123 @@
+//!
+//! Posts notifications to HTTP webhooks, such as the incoming webhooks of Slack or Teams.
+//!
+
+use super::{Notification, Notifier};
+use crate::error::{Error, Result};
+use std::time::Duration;
+
+///
+/// POSTs every notification as a JSON object `{"title": <subject>, "text": <body>}`, which
+/// Slack and Teams incoming webhooks both accept. Slack only shows the text.
+///
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl WebhookNotifier {
+    ///
+    /// # Errors
+    /// `Error::Config` if the client cannot be set up.
+    ///
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| Error::Config(format!("cannot create the webhook client: {}", e)))?;
+        Ok(WebhookNotifier {
+            url: url.to_string(),
+            client,
+        })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let payload = serde_json::json!({
+            "title": notification.subject,
+            "text": notification.body,
+        });
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.to_string())
+            .send()
+            .map_err(|e| Error::Network(format!("webhook {}: {}", self.url, e)))?;
+        if !response.status().is_success() {
+            return Err(Error::Network(format!(
+                "webhook {} answered {}",
+                self.url,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    ///
+    /// Accepts one HTTP request, answers it with `status` and returns its body.
+    ///
+    fn serve_once(status: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                let lower = line.to_ascii_lowercase();
+                if let Some(value) = lower.strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_webhook_notifier() {
+        let notification = Notification {
+            subject: "MSFT: breakout".to_string(),
+            body: "breakout: MSFT at 251.5".to_string(),
+        };
+
+        let (url, server) = serve_once("200 OK");
+        let notifier = WebhookNotifier::new(&url, Duration::from_secs(5)).unwrap();
+        notifier.notify(&notification).unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({"title": "MSFT: breakout", "text": "breakout: MSFT at 251.5"})
+        );
+
+        let (url, server) = serve_once("500 Internal Server Error");
+        let notifier = WebhookNotifier::new(&url, Duration::from_secs(5)).unwrap();
+        assert!(matches!(
+            notifier.notify(&notification),
+            Err(Error::Network(_))
+        ));
+        server.join().unwrap();
+    }
+}
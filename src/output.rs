@@ -0,0 +1,293 @@
+//!
+//! Writes records as CSV, JSON, newline-delimited JSON, a terminal table or Parquet.
+//!
+
+use crate::error::{Error, Result};
+use chrono::prelude::*;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+#[cfg(feature = "parquet")]
+mod parquet_file;
+mod table;
+
+///
+/// A single typed value of a record.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Timestamp(DateTime<Utc>),
+    Text(String),
+    /// A raw number; `None` if it could not be computed, e.g. for lack of data
+    Number(Option<f64>),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Timestamp(t) => write!(f, "{}", t.to_rfc3339()),
+            Field::Text(s) => write!(f, "{}", s),
+            Field::Number(Some(n)) => write!(f, "{}", n),
+            Field::Number(None) => Ok(()),
+        }
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Field::Timestamp(t) => serializer.serialize_str(&t.to_rfc3339()),
+            Field::Text(s) => serializer.serialize_str(s),
+            Field::Number(Some(n)) if n.is_finite() => serializer.serialize_f64(*n),
+            Field::Number(_) => serializer.serialize_none(),
+        }
+    }
+}
+
+///
+/// Anything that can be written as a row of named fields. All records written to the same
+/// writer have to return the same field names in the same order.
+///
+pub trait Record {
+    fn fields(&self) -> Vec<(String, Field)>;
+}
+
+///
+/// Serializes the fields of a record as a JSON object, keeping the field order.
+///
+struct JsonRecord(Vec<(String, Field)>);
+
+impl Serialize for JsonRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, field) in &self.0 {
+            map.serialize_entry(name, field)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// A single JSON array of objects
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// An aligned table for reading in a terminal
+    Table,
+    /// An Apache Parquet file, only available with the `parquet` feature
+    Parquet,
+}
+
+impl OutputFormat {
+    ///
+    /// Whether records are written as soon as they arrive, rather than when the writer finishes.
+    ///
+    pub fn is_streaming(&self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Ndjson)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "table" => Ok(OutputFormat::Table),
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+///
+/// Writes records in one of the output formats.
+///
+pub trait RecordWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()>;
+
+    ///
+    /// Writes out buffered records and flushes the underlying writer.
+    ///
+    fn finish(&mut self) -> Result<()>;
+}
+
+///
+/// Creates a writer for `format` on top of `out`.
+///
+pub fn create_writer(
+    format: OutputFormat,
+    out: Box<dyn Write + Send>,
+) -> Result<Box<dyn RecordWriter>> {
+    match format {
+        OutputFormat::Csv => Ok(Box::new(CsvWriter {
+            writer: csv::Writer::from_writer(out),
+            header_written: false,
+        })),
+        OutputFormat::Json => Ok(Box::new(JsonWriter {
+            out,
+            records: Vec::new(),
+        })),
+        OutputFormat::Ndjson => Ok(Box::new(NdjsonWriter { out })),
+        OutputFormat::Table => Ok(Box::new(table::TableWriter::new(out))),
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => Ok(Box::new(parquet_file::ParquetWriter::new(out))),
+        #[cfg(not(feature = "parquet"))]
+        OutputFormat::Parquet => Err(Error::Config(
+            "parquet output requires building with the 'parquet' feature".to_string(),
+        )),
+    }
+}
+
+struct CsvWriter {
+    writer: csv::Writer<Box<dyn Write + Send>>,
+    header_written: bool,
+}
+
+impl RecordWriter for CsvWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()> {
+        let fields = record.fields();
+        if !self.header_written {
+            self.writer
+                .write_record(fields.iter().map(|(name, _)| name))?;
+            self.header_written = true;
+        }
+        self.writer
+            .write_record(fields.iter().map(|(_, field)| field.to_string()))?;
+        // flush every row so rows show up immediately when watching
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+struct JsonWriter {
+    out: Box<dyn Write + Send>,
+    records: Vec<JsonRecord>,
+}
+
+impl RecordWriter for JsonWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()> {
+        self.records.push(JsonRecord(record.fields()));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        serde_json::to_writer_pretty(&mut self.out, &self.records)
+            .map_err(|e| Error::Io(e.into()))?;
+        writeln!(self.out)?;
+        self.records.clear();
+        Ok(self.out.flush()?)
+    }
+}
+
+struct NdjsonWriter {
+    out: Box<dyn Write + Send>,
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &JsonRecord(record.fields()))
+            .map_err(|e| Error::Io(e.into()))?;
+        writeln!(self.out)?;
+        Ok(self.out.flush()?)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Row(&'static str, Option<f64>);
+
+    impl Record for Row {
+        fn fields(&self) -> Vec<(String, Field)> {
+            vec![
+                (
+                    "time".to_string(),
+                    Field::Timestamp(Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap()),
+                ),
+                ("symbol".to_string(), Field::Text(self.0.to_string())),
+                ("value".to_string(), Field::Number(self.1)),
+            ]
+        }
+    }
+
+    /// A `Write` whose contents can be inspected after the writer took ownership of it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let out = Shared::default();
+        let mut writer = create_writer(format, Box::new(out.clone())).unwrap();
+        writer.write(&Row("MSFT", Some(1.5))).unwrap();
+        writer.write(&Row("IBM", None)).unwrap();
+        writer.finish().unwrap();
+
+        let bytes = out.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            render(OutputFormat::Csv),
+            "time,symbol,value\n\
+             2021-01-04T00:00:00+00:00,MSFT,1.5\n\
+             2021-01-04T00:00:00+00:00,IBM,\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson() {
+        assert_eq!(
+            render(OutputFormat::Ndjson),
+            "{\"time\":\"2021-01-04T00:00:00+00:00\",\"symbol\":\"MSFT\",\"value\":1.5}\n\
+             {\"time\":\"2021-01-04T00:00:00+00:00\",\"symbol\":\"IBM\",\"value\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json: serde_json::Value = serde_json::from_str(&render(OutputFormat::Json)).unwrap();
+        assert_eq!(json[0]["symbol"], "MSFT");
+        assert_eq!(json[0]["value"], 1.5);
+        assert_eq!(json[1]["value"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            render(OutputFormat::Table),
+            "time                       symbol  value\n\
+             -------------------------  ------  -----\n\
+             2021-01-04T00:00:00+00:00  MSFT     1.50\n\
+             2021-01-04T00:00:00+00:00  IBM\n"
+        );
+    }
+}
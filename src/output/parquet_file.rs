@@ -0,0 +1,223 @@
+use super::{Field, Record, RecordWriter};
+use crate::error::{Error, Result};
+use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use std::io::Write;
+use std::sync::Arc;
+
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        Error::Io(std::io::Error::other(e))
+    }
+}
+
+///
+/// Buffers all records and writes them as a single row group once finished.
+///
+/// Timestamps become millisecond `INT64` timestamps, text becomes `UTF8` byte arrays and
+/// numbers become optional doubles.
+///
+pub(super) struct ParquetWriter {
+    out: Option<Box<dyn Write + Send>>,
+    records: Vec<Vec<(String, Field)>>,
+}
+
+impl ParquetWriter {
+    pub(super) fn new(out: Box<dyn Write + Send>) -> Self {
+        ParquetWriter {
+            out: Some(out),
+            records: Vec::new(),
+        }
+    }
+}
+
+///
+/// The column for `field`, built directly so that names like `BRK.B` need no escaping.
+///
+fn column_type(name: &str, field: &Field) -> Result<Arc<Type>> {
+    let (physical, repetition, logical) = match field {
+        Field::Timestamp(_) => (
+            PhysicalType::INT64,
+            Repetition::REQUIRED,
+            Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: true,
+                unit: TimeUnit::MILLIS,
+            }),
+        ),
+        Field::Text(_) => (
+            PhysicalType::BYTE_ARRAY,
+            Repetition::REQUIRED,
+            Some(LogicalType::String),
+        ),
+        Field::Number(_) => (PhysicalType::DOUBLE, Repetition::OPTIONAL, None),
+    };
+    Ok(Arc::new(
+        Type::primitive_type_builder(name, physical)
+            .with_repetition(repetition)
+            .with_logical_type(logical)
+            .build()?,
+    ))
+}
+
+impl RecordWriter for ParquetWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()> {
+        self.records.push(record.fields());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let (first, out) = match (self.records.first(), self.out.take()) {
+            (Some(first), Some(out)) => (first, out),
+            _ => return Ok(()),
+        };
+
+        if let Some(i) = self.records.iter().position(|r| r.len() != first.len()) {
+            return Err(Error::Config(format!(
+                "parquet needs the same columns in every row, but row {} has {} fields instead of {}",
+                i + 1,
+                self.records[i].len(),
+                first.len()
+            )));
+        }
+        let columns = first
+            .iter()
+            .map(|(name, field)| column_type(name, field))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Type::group_type_builder("summary")
+            .with_fields(columns)
+            .build()?;
+        let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Default::default())?;
+        let mut row_group = writer.next_row_group()?;
+
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            let values = self.records.iter().map(|r| &r[index].1);
+            match &first[index].1 {
+                Field::Timestamp(_) => {
+                    let values: Vec<i64> = values
+                        .map(|v| match v {
+                            Field::Timestamp(t) => t.timestamp_millis(),
+                            _ => 0,
+                        })
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                Field::Text(_) => {
+                    let values: Vec<ByteArray> = values
+                        .map(|v| ByteArray::from(v.to_string().as_str()))
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                Field::Number(_) => {
+                    let numbers: Vec<Option<f64>> = values
+                        .map(|v| match v {
+                            Field::Number(n) => *n,
+                            _ => None,
+                        })
+                        .collect();
+                    let present: Vec<f64> = numbers.iter().flatten().copied().collect();
+                    let levels: Vec<i16> = numbers.iter().map(|n| n.is_some() as i16).collect();
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&present, Some(&levels), None)?;
+                }
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        writer.close()?;
+        self.records.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    struct Row(&'static str, Option<f64>);
+
+    impl Record for Row {
+        fn fields(&self) -> Vec<(String, Field)> {
+            vec![
+                (
+                    "time".to_string(),
+                    Field::Timestamp(Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap()),
+                ),
+                ("symbol".to_string(), Field::Text(self.0.to_string())),
+                ("value".to_string(), Field::Number(self.1)),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("stock-tracker-{}.parquet", std::process::id()));
+        let mut writer = ParquetWriter::new(Box::new(std::fs::File::create(&path).unwrap()));
+        writer.write(&Row("MSFT", Some(1.5))).unwrap();
+        writer.write(&Row("IBM", None)).unwrap();
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let rows: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert!(rows[0].contains("symbol: \"MSFT\"") && rows[0].contains("value: 1.5"));
+        assert!(rows[1].contains("symbol: \"IBM\"") && rows[1].contains("value: null"));
+    }
+
+    struct Columns(Vec<&'static str>);
+
+    impl Record for Columns {
+        fn fields(&self) -> Vec<(String, Field)> {
+            self.0
+                .iter()
+                .map(|name| (name.to_string(), Field::Number(Some(1.0))))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_parquet_column_names() {
+        let path = std::env::temp_dir().join(format!(
+            "stock-tracker-names-{}.parquet",
+            std::process::id()
+        ));
+        let names = vec!["BRK.B", "^GSPC", "bb_upper_20_2.5", "a b;c"];
+        let mut writer = ParquetWriter::new(Box::new(std::fs::File::create(&path).unwrap()));
+        writer.write(&Columns(names.clone())).unwrap();
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let columns: Vec<String> = (0..schema.num_columns())
+            .map(|i| schema.column(i).name().to_string())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(columns, names);
+    }
+
+    #[test]
+    fn test_parquet_rows_with_other_columns() {
+        let mut writer = ParquetWriter::new(Box::new(Vec::new()));
+        writer.write(&Columns(vec!["a", "b"])).unwrap();
+        writer.write(&Columns(vec!["a"])).unwrap();
+        assert!(matches!(writer.finish(), Err(Error::Config(_))));
+    }
+}
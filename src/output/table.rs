@@ -0,0 +1,80 @@
+use super::{Field, Record, RecordWriter};
+use crate::error::Result;
+use std::io::Write;
+
+///
+/// Buffers all records and prints them as aligned columns once finished.
+///
+/// Numbers are rounded to two decimals and right aligned, everything else is left aligned.
+///
+pub(super) struct TableWriter {
+    out: Box<dyn Write + Send>,
+    header: Vec<String>,
+    rows: Vec<Vec<(String, bool)>>,
+}
+
+impl TableWriter {
+    pub(super) fn new(out: Box<dyn Write + Send>) -> Self {
+        TableWriter {
+            out,
+            header: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+fn cell(field: &Field) -> (String, bool) {
+    match field {
+        Field::Number(Some(n)) => (format!("{:.2}", n), true),
+        Field::Number(None) => (String::new(), true),
+        field => (field.to_string(), false),
+    }
+}
+
+impl RecordWriter for TableWriter {
+    fn write(&mut self, record: &dyn Record) -> Result<()> {
+        let fields = record.fields();
+        if self.header.is_empty() {
+            self.header = fields.iter().map(|(name, _)| name.clone()).collect();
+        }
+        self.rows
+            .push(fields.iter().map(|(_, field)| cell(field)).collect());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.header.is_empty() {
+            return Ok(());
+        }
+
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (width, (text, _)) in widths.iter_mut().zip(row) {
+                *width = (*width).max(text.len());
+            }
+        }
+
+        let header: Vec<(String, bool)> = self.header.iter().map(|h| (h.clone(), false)).collect();
+        let rule: Vec<(String, bool)> = widths.iter().map(|w| ("-".repeat(*w), false)).collect();
+        for row in std::iter::once(&header)
+            .chain(std::iter::once(&rule))
+            .chain(&self.rows)
+        {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|((text, right), width)| {
+                    if *right {
+                        format!("{:>width$}", text, width = width)
+                    } else {
+                        format!("{:<width$}", text, width = width)
+                    }
+                })
+                .collect();
+            writeln!(self.out, "{}", line.join("  ").trim_end())?;
+        }
+        self.rows.clear();
+
+        Ok(self.out.flush()?)
+    }
+}
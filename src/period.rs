@@ -0,0 +1,259 @@
+//!
+//! The period quotes are fetched for, given as dates, durations back from now or named calendar
+//! periods.
+//!
+
+use crate::error::{Error, Result};
+use crate::provider::csv_file::parse_timestamp;
+use chrono::prelude::*;
+use chrono::{Duration, Months};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NamedPeriod {
+    Today,
+    /// Year, quarter and month to date
+    Ytd,
+    Qtd,
+    Mtd,
+    LastMonth,
+    LastQuarter,
+    LastYear,
+}
+
+///
+/// A start or end of a period as given on the command line.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateSpec {
+    Now,
+    /// An exact point in time
+    At(DateTime<Utc>),
+    /// A whole day in UTC, starting at midnight and ending just before the next one
+    Day(NaiveDate),
+    /// A duration before now, e.g. `6mo`
+    Ago(u32, Unit),
+    Named(NamedPeriod),
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN))
+}
+
+fn first_of_month(now: DateTime<Utc>, month: u32) -> DateTime<Utc> {
+    midnight(NaiveDate::from_ymd_opt(now.year(), month, 1).expect("a valid month"))
+}
+
+fn months_before(t: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    t.checked_sub_months(Months::new(months))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+impl NamedPeriod {
+    ///
+    /// The first instant of the period and its last one, which is `now` for periods to date.
+    ///
+    fn bounds(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let year = first_of_month(now, 1);
+        let quarter = first_of_month(now, (now.month0() / 3) * 3 + 1);
+        let month = first_of_month(now, now.month());
+        // the periods before the current one end just before it starts
+        let before = |start: DateTime<Utc>| start - Duration::seconds(1);
+        match self {
+            NamedPeriod::Today => (midnight(now.date_naive()), now),
+            NamedPeriod::Ytd => (year, now),
+            NamedPeriod::Qtd => (quarter, now),
+            NamedPeriod::Mtd => (month, now),
+            NamedPeriod::LastMonth => (months_before(month, 1), before(month)),
+            NamedPeriod::LastQuarter => (months_before(quarter, 3), before(quarter)),
+            NamedPeriod::LastYear => (months_before(year, 12), before(year)),
+        }
+    }
+}
+
+impl DateSpec {
+    ///
+    /// The instant the spec starts at, relative to `now`.
+    ///
+    pub fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            DateSpec::Day(date) => midnight(*date),
+            DateSpec::Named(period) => period.bounds(now).0,
+            _ => self.end(now),
+        }
+    }
+
+    ///
+    /// The last instant the spec covers, relative to `now`.
+    ///
+    pub fn end(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            DateSpec::Now => now,
+            DateSpec::At(t) => *t,
+            DateSpec::Day(date) => midnight(*date) + Duration::days(1) - Duration::seconds(1),
+            DateSpec::Ago(n, Unit::Days) => now - Duration::days(*n as i64),
+            DateSpec::Ago(n, Unit::Weeks) => now - Duration::weeks(*n as i64),
+            DateSpec::Ago(n, Unit::Months) => months_before(now, *n),
+            DateSpec::Ago(n, Unit::Years) => months_before(now, n.saturating_mul(12)),
+            DateSpec::Named(period) => period.bounds(now).1,
+        }
+    }
+}
+
+impl FromStr for DateSpec {
+    type Err = String;
+
+    ///
+    /// Parses `now`, an RFC3339 timestamp, a `YYYY-MM-DD` date, unix seconds, a duration back
+    /// from now in days, weeks, months or years (`10d`, `4w`, `6mo`, `1y`) or one of `today`,
+    /// `ytd`, `qtd`, `mtd`, `last-month`, `last-quarter` and `last-year`.
+    ///
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let named = match s.to_ascii_lowercase().as_str() {
+            "now" => return Ok(DateSpec::Now),
+            "today" => Some(NamedPeriod::Today),
+            "ytd" => Some(NamedPeriod::Ytd),
+            "qtd" => Some(NamedPeriod::Qtd),
+            "mtd" => Some(NamedPeriod::Mtd),
+            "last-month" => Some(NamedPeriod::LastMonth),
+            "last-quarter" => Some(NamedPeriod::LastQuarter),
+            "last-year" => Some(NamedPeriod::LastYear),
+            _ => None,
+        };
+        if let Some(named) = named {
+            return Ok(DateSpec::Named(named));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(DateSpec::Day(date));
+        }
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let unit = match unit {
+            "d" => Some(Unit::Days),
+            "w" => Some(Unit::Weeks),
+            "mo" => Some(Unit::Months),
+            "y" => Some(Unit::Years),
+            _ => None,
+        };
+        if let (Some(unit), Ok(value)) = (unit, value.parse()) {
+            return Ok(DateSpec::Ago(value, unit));
+        }
+
+        parse_timestamp(s).map(DateSpec::At).map_err(|_| {
+            format!(
+                "invalid date '{}', expected a date, a timestamp, a duration like 6mo or 1y, or one of today, ytd, qtd, mtd, last-month, last-quarter or last-year",
+                s
+            )
+        })
+    }
+}
+
+///
+/// A resolved period, with both ends included.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Period {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Period {
+    ///
+    /// Resolves the specs relative to `now`. Without `to`, a named period ends where it ends
+    /// and everything else at `now`.
+    ///
+    /// # Errors
+    /// `Error::Config` unless the start is before the end.
+    ///
+    pub fn resolve(from: &DateSpec, to: Option<&DateSpec>, now: DateTime<Utc>) -> Result<Period> {
+        let period = Period {
+            from: from.start(now),
+            to: match (to, from) {
+                (Some(to), _) => to.end(now),
+                (None, DateSpec::Named(_)) => from.end(now),
+                (None, _) => now,
+            },
+        };
+        if period.from >= period.to {
+            return Err(Error::Config(format!(
+                "the period has to start before it ends, but starts at {} and ends at {}",
+                period.from.to_rfc3339(),
+                period.to.to_rfc3339()
+            )));
+        }
+        Ok(period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_date_spec() {
+        assert_eq!("now".parse(), Ok(DateSpec::Now));
+        assert_eq!(
+            "2024-01-01".parse(),
+            Ok(DateSpec::Day(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()))
+        );
+        assert_eq!(
+            "2024-01-01T12:00:00Z".parse(),
+            Ok(DateSpec::At(at(2024, 1, 1, 12, 0, 0)))
+        );
+        assert_eq!("6mo".parse(), Ok(DateSpec::Ago(6, Unit::Months)));
+        assert_eq!("1y".parse(), Ok(DateSpec::Ago(1, Unit::Years)));
+        assert_eq!(
+            "Last-Quarter".parse(),
+            Ok(DateSpec::Named(NamedPeriod::LastQuarter))
+        );
+        for invalid in ["6m", "y", "2024-13-01", "someday"].iter() {
+            assert!(invalid.parse::<DateSpec>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let now = at(2024, 5, 31, 15, 0, 0);
+        let resolve = |from: &str, to: Option<&str>| {
+            let to: Option<DateSpec> = to.map(|to| to.parse().unwrap());
+            Period::resolve(&from.parse().unwrap(), to.as_ref(), now)
+        };
+
+        let ytd = resolve("ytd", None).unwrap();
+        assert_eq!((ytd.from, ytd.to), (at(2024, 1, 1, 0, 0, 0), now));
+        let quarter = resolve("last-quarter", None).unwrap();
+        assert_eq!(
+            (quarter.from, quarter.to),
+            (at(2024, 1, 1, 0, 0, 0), at(2024, 3, 31, 23, 59, 59))
+        );
+        let year = resolve("last-year", Some("now")).unwrap();
+        assert_eq!((year.from, year.to), (at(2023, 1, 1, 0, 0, 0), now));
+        // the end of February is clamped
+        assert_eq!(
+            resolve("3mo", None).unwrap().from,
+            at(2024, 2, 29, 15, 0, 0)
+        );
+        let days = resolve("2024-01-01", Some("2024-01-31")).unwrap();
+        assert_eq!(days.to, at(2024, 1, 31, 23, 59, 59));
+
+        assert!(matches!(
+            resolve("2024-02-01", Some("2024-01-01")),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(resolve("now", None), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,447 @@
+//!
+//! Holdings read from a portfolio file, valued at the latest quotes.
+//!
+
+use crate::error::{Error, Result};
+use crate::model::Bar;
+use crate::output::{Field, Record};
+use crate::provider::csv_file::parse_timestamp;
+use chrono::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+///
+/// A position: a quantity of a symbol bought at a price.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    pub symbol: String,
+    pub quantity: f64,
+    /// The price paid per share
+    pub cost_basis: f64,
+    pub purchase_date: Option<DateTime<Utc>>,
+}
+
+///
+/// One holding as written in a portfolio file.
+///
+#[derive(Debug, Deserialize)]
+struct HoldingRow {
+    symbol: String,
+    quantity: f64,
+    cost_basis: f64,
+    #[serde(default)]
+    purchase_date: Option<DateText>,
+}
+
+///
+/// A TOML date or a date as text, like in the quote files.
+///
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DateText {
+    Date(toml::value::Datetime),
+    Text(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlPortfolio {
+    holdings: Vec<HoldingRow>,
+}
+
+impl HoldingRow {
+    fn into_holding(self) -> Result<Holding> {
+        if self.symbol.trim().is_empty() {
+            return Err(Error::Parse("a holding has an empty symbol".to_string()));
+        }
+        if !self.quantity.is_finite() || self.quantity == 0.0 {
+            return Err(Error::Parse(format!(
+                "invalid quantity {} for '{}'",
+                self.quantity, self.symbol
+            )));
+        }
+        if !self.cost_basis.is_finite() || self.cost_basis < 0.0 {
+            return Err(Error::Parse(format!(
+                "invalid cost basis {} for '{}'",
+                self.cost_basis, self.symbol
+            )));
+        }
+
+        let purchase_date = match self.purchase_date {
+            Some(DateText::Date(date)) => Some(parse_timestamp(&date.to_string())?),
+            Some(DateText::Text(text)) if !text.trim().is_empty() => Some(parse_timestamp(&text)?),
+            _ => None,
+        };
+        Ok(Holding {
+            symbol: self.symbol.trim().to_string(),
+            quantity: self.quantity,
+            cost_basis: self.cost_basis,
+            purchase_date,
+        })
+    }
+}
+
+///
+/// The holdings of a portfolio. A symbol may be held several times, e.g. when it was bought in
+/// several lots.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Portfolio {
+    pub holdings: Vec<Holding>,
+}
+
+impl Portfolio {
+    ///
+    /// Reads a portfolio file, either TOML with a `[[holdings]]` table per holding or CSV with
+    /// a header row, depending on the extension. Both use the columns `symbol`, `quantity`,
+    /// `cost_basis` and the optional `purchase_date`.
+    ///
+    pub fn load(path: &Path) -> Result<Portfolio> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("toml") => Portfolio::from_toml(&std::fs::read_to_string(path)?),
+            Some("csv") => Portfolio::from_csv(std::fs::File::open(path)?),
+            _ => Err(Error::Config(format!(
+                "unknown portfolio file type '{}', expected .toml or .csv",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Portfolio> {
+        let file: TomlPortfolio = toml::from_str(s).map_err(|e| Error::Parse(e.to_string()))?;
+        Ok(Portfolio {
+            holdings: file
+                .holdings
+                .into_iter()
+                .map(HoldingRow::into_holding)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn from_csv<R: Read>(reader: R) -> Result<Portfolio> {
+        let mut holdings = Vec::new();
+        for row in csv::Reader::from_reader(reader).deserialize() {
+            let row: HoldingRow = row?;
+            holdings.push(row.into_holding()?);
+        }
+        Ok(Portfolio { holdings })
+    }
+
+    ///
+    /// The held symbols without duplicates, in the order they first appear.
+    ///
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = Vec::new();
+        for holding in &self.holdings {
+            if !symbols.contains(&holding.symbol) {
+                symbols.push(holding.symbol.clone());
+            }
+        }
+        symbols
+    }
+}
+
+///
+/// The symbol of the row summing up all positions.
+///
+pub const TOTAL: &str = "TOTAL";
+
+///
+/// The valuation of a position, or of the whole portfolio in the total row, which leaves the
+/// per-share columns empty.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionRow {
+    pub symbol: String,
+    pub quantity: Option<f64>,
+    pub cost_basis: Option<f64>,
+    /// The latest close
+    pub price: Option<f64>,
+    pub market_value: f64,
+    pub cost: f64,
+    pub unrealized_pnl: f64,
+    /// The unrealized profit relative to the cost in percent
+    pub unrealized_pnl_pct: Option<f64>,
+    /// The share of the total market value in percent
+    pub weight_pct: f64,
+    /// The return of the latest bar in percent
+    pub daily_return_pct: Option<f64>,
+    /// The return since the start of the period, or the purchase if that was later, in percent
+    pub period_return_pct: Option<f64>,
+}
+
+impl Record for PositionRow {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("quantity".to_string(), Field::Number(self.quantity)),
+            ("cost_basis".to_string(), Field::Number(self.cost_basis)),
+            ("price".to_string(), Field::Number(self.price)),
+            (
+                "market_value".to_string(),
+                Field::Number(Some(self.market_value)),
+            ),
+            ("cost".to_string(), Field::Number(Some(self.cost))),
+            (
+                "unrealized_pnl".to_string(),
+                Field::Number(Some(self.unrealized_pnl)),
+            ),
+            (
+                "unrealized_pnl_pct".to_string(),
+                Field::Number(self.unrealized_pnl_pct),
+            ),
+            (
+                "weight_pct".to_string(),
+                Field::Number(Some(self.weight_pct)),
+            ),
+            (
+                "daily_return_pct".to_string(),
+                Field::Number(self.daily_return_pct),
+            ),
+            (
+                "period_return_pct".to_string(),
+                Field::Number(self.period_return_pct),
+            ),
+        ]
+    }
+}
+
+///
+/// A return together with the market value it was earned on, so returns of several positions
+/// can be weighted.
+///
+#[derive(Debug, Clone, Copy)]
+struct WeightedReturn {
+    value: f64,
+    base: f64,
+}
+
+fn weighted_total(returns: &[WeightedReturn]) -> Option<f64> {
+    let base: f64 = returns.iter().map(|r| r.base).sum();
+    if returns.is_empty() || base == 0.0 {
+        return None;
+    }
+    Some(returns.iter().map(|r| r.value * r.base).sum::<f64>() / base)
+}
+
+///
+/// Values every holding at the latest bar of its symbol in `history`, skipping holdings without
+/// bars, and appends a total row.
+///
+/// Market values and profits use the traded closes, returns use the adjusted closes so that
+/// dividends and splits do not show up as losses.
+///
+pub fn valuation(
+    portfolio: &Portfolio,
+    history: &HashMap<String, Vec<Bar>>,
+    period_start: DateTime<Utc>,
+) -> Vec<PositionRow> {
+    let mut rows = Vec::new();
+    let mut daily = Vec::new();
+    let mut period = Vec::new();
+
+    for holding in &portfolio.holdings {
+        let bars = match history.get(&holding.symbol) {
+            Some(bars) => bars,
+            None => continue,
+        };
+        let last = match bars.last() {
+            Some(last) => last,
+            None => continue,
+        };
+
+        let daily_return = bars.len().checked_sub(2).map(|i| WeightedReturn {
+            value: last.adjclose / bars[i].adjclose - 1.0,
+            base: holding.quantity * bars[i].close,
+        });
+        let start = holding
+            .purchase_date
+            .map_or(period_start, |date| date.max(period_start));
+        let period_return =
+            bars.iter()
+                .find(|b| b.timestamp >= start)
+                .map(|first| WeightedReturn {
+                    value: last.adjclose / first.adjclose - 1.0,
+                    base: holding.quantity * first.close,
+                });
+        daily.extend(daily_return);
+        period.extend(period_return);
+
+        let market_value = holding.quantity * last.close;
+        let cost = holding.quantity * holding.cost_basis;
+        rows.push(PositionRow {
+            symbol: holding.symbol.clone(),
+            quantity: Some(holding.quantity),
+            cost_basis: Some(holding.cost_basis),
+            price: Some(last.close),
+            market_value,
+            cost,
+            unrealized_pnl: market_value - cost,
+            unrealized_pnl_pct: pnl_pct(market_value, cost),
+            weight_pct: 0.0,
+            daily_return_pct: daily_return.map(|r| r.value * 100.0),
+            period_return_pct: period_return.map(|r| r.value * 100.0),
+        });
+    }
+
+    let market_value: f64 = rows.iter().map(|r| r.market_value).sum();
+    let cost: f64 = rows.iter().map(|r| r.cost).sum();
+    for row in &mut rows {
+        row.weight_pct = share_pct(row.market_value, market_value);
+    }
+    rows.push(PositionRow {
+        symbol: TOTAL.to_string(),
+        quantity: None,
+        cost_basis: None,
+        price: None,
+        market_value,
+        cost,
+        unrealized_pnl: market_value - cost,
+        unrealized_pnl_pct: pnl_pct(market_value, cost),
+        weight_pct: share_pct(market_value, market_value),
+        daily_return_pct: weighted_total(&daily).map(|r| r * 100.0),
+        period_return_pct: weighted_total(&period).map(|r| r * 100.0),
+    });
+    rows
+}
+
+fn pnl_pct(market_value: f64, cost: f64) -> Option<f64> {
+    if cost == 0.0 {
+        None
+    } else {
+        Some((market_value - cost) / cost.abs() * 100.0)
+    }
+}
+
+fn share_pct(value: f64, total: f64) -> f64 {
+    if total == 0.0 {
+        0.0
+    } else {
+        value / total * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(day: u32, close: f64) -> Bar {
+        Bar {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            adjclose: close,
+            volume: 0,
+        }
+    }
+
+    #[test]
+    fn test_load_toml_and_csv() {
+        let toml = r#"
+            [[holdings]]
+            symbol = "MSFT"
+            quantity = 10
+            cost_basis = 200.5
+            purchase_date = 2021-01-04
+
+            [[holdings]]
+            symbol = "IBM"
+            quantity = 2.5
+            cost_basis = 120
+            purchase_date = "2021-02-01T15:30:00Z"
+
+            [[holdings]]
+            symbol = "MSFT"
+            quantity = 5
+            cost_basis = 230
+        "#;
+        let portfolio = Portfolio::from_toml(toml).unwrap();
+        assert_eq!(portfolio.holdings.len(), 3);
+        assert_eq!(portfolio.holdings[0].quantity, 10.0);
+        assert_eq!(
+            portfolio.holdings[0].purchase_date,
+            Some(Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            portfolio.holdings[1].purchase_date,
+            Some(Utc.with_ymd_and_hms(2021, 2, 1, 15, 30, 0).unwrap())
+        );
+        assert_eq!(portfolio.symbols(), vec!["MSFT", "IBM"]);
+
+        let csv = "symbol,quantity,cost_basis,purchase_date\n\
+                   MSFT,10,200.5,2021-01-04\n\
+                   IBM,2.5,120,2021-02-01T15:30:00Z\n\
+                   MSFT,5,230,\n";
+        assert_eq!(Portfolio::from_csv(csv.as_bytes()).unwrap(), portfolio);
+
+        assert!(Portfolio::from_csv("symbol,quantity,cost_basis\nMSFT,0,1\n".as_bytes()).is_err());
+        assert!(Portfolio::from_toml("[[holdings]]\nsymbol = \"MSFT\"\n").is_err());
+        assert!(Portfolio::load(Path::new("portfolio.json")).is_err());
+    }
+
+    #[test]
+    fn test_valuation() {
+        let portfolio = Portfolio {
+            holdings: vec![
+                Holding {
+                    symbol: "MSFT".to_string(),
+                    quantity: 10.0,
+                    cost_basis: 100.0,
+                    purchase_date: None,
+                },
+                Holding {
+                    symbol: "IBM".to_string(),
+                    quantity: 5.0,
+                    cost_basis: 50.0,
+                    // bought after the start of the period
+                    purchase_date: Some(Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap()),
+                },
+                Holding {
+                    symbol: "NOPE".to_string(),
+                    quantity: 1.0,
+                    cost_basis: 1.0,
+                    purchase_date: None,
+                },
+            ],
+        };
+        let mut history = HashMap::new();
+        history.insert(
+            "MSFT".to_string(),
+            vec![bar(1, 100.0), bar(2, 110.0), bar(3, 121.0)],
+        );
+        history.insert(
+            "IBM".to_string(),
+            vec![bar(1, 60.0), bar(2, 40.0), bar(3, 38.0)],
+        );
+        let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let rows = valuation(&portfolio, &history, start);
+
+        assert_eq!(rows.len(), 3);
+        let (msft, ibm, total) = (&rows[0], &rows[1], &rows[2]);
+        assert_eq!(msft.market_value, 1210.0);
+        assert_eq!(msft.unrealized_pnl, 210.0);
+        assert_eq!(msft.unrealized_pnl_pct, Some(21.0));
+        assert!((msft.daily_return_pct.unwrap() - 10.0).abs() < 1e-9);
+        assert!((msft.period_return_pct.unwrap() - 21.0).abs() < 1e-9);
+        assert_eq!(ibm.market_value, 190.0);
+        assert!((ibm.period_return_pct.unwrap() + 5.0).abs() < 1e-9);
+
+        assert_eq!(total.symbol, TOTAL);
+        assert_eq!(total.market_value, 1400.0);
+        assert_eq!(total.cost, 1250.0);
+        assert_eq!(total.weight_pct, 100.0);
+        assert!((msft.weight_pct + ibm.weight_pct - 100.0).abs() < 1e-9);
+        // (1100 * 10% + 200 * -5%) / 1300
+        assert!((total.daily_return_pct.unwrap() - 100.0 / 1300.0 * 100.0).abs() < 1e-9);
+        // (1000 * 21% + 200 * -5%) / 1200
+        assert!((total.period_return_pct.unwrap() - 200.0 / 1200.0 * 100.0).abs() < 1e-9);
+    }
+}
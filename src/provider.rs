@@ -0,0 +1,126 @@
+use crate::error::Error;
+use crate::model::{Bar, BarInterval, Event};
+use crate::resample::resample;
+use chrono::prelude::*;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+pub mod adjusted;
+pub mod cache;
+pub mod csv_file;
+pub mod yahoo;
+
+/// The result of a provider call.
+pub type ProviderResult<T> = Result<T, Error>;
+
+///
+/// A source of historical quotes.
+///
+/// Implementations return the bars for `symbol` between `from` and `to`, sorted by timestamp:
+/// their own bars from `fetch_history`, daily for remote providers, and bars of a given
+/// interval from `fetch_bars`. Providers are shared between the threads fetching symbols
+/// concurrently.
+///
+pub trait QuoteProvider: Send + Sync {
+    fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Bar>>;
+
+    ///
+    /// Returns the bars of `symbol` at `interval` between `from` and `to`, sorted by timestamp.
+    /// By default the daily bars are resampled, so providers only implement this if they offer
+    /// other intervals themselves.
+    ///
+    /// # Errors
+    /// `Error::Config` for intraday intervals the provider does not offer.
+    ///
+    fn fetch_bars(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: BarInterval,
+    ) -> ProviderResult<Vec<Bar>> {
+        if interval.is_intraday() {
+            return Err(Error::Config(format!(
+                "the provider only offers daily bars, not {}",
+                interval
+            )));
+        }
+        Ok(resample(&self.fetch_history(symbol, from, to)?, interval))
+    }
+
+    ///
+    /// Returns the dividends and splits of `symbol` between `from` and `to`, sorted by
+    /// timestamp. Providers that know nothing about corporate actions return none.
+    ///
+    fn fetch_events(
+        &self,
+        _symbol: &str,
+        _from: DateTime<Utc>,
+        _to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Event>> {
+        Ok(Vec::new())
+    }
+}
+
+///
+/// The providers selectable from the command line.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    Yahoo,
+    Csv,
+}
+
+impl ProviderKind {
+    ///
+    /// Creates the provider. `data` is the recorded quote file or directory used by file-backed providers.
+    ///
+    pub fn build(&self, data: Option<&Path>) -> ProviderResult<Box<dyn QuoteProvider>> {
+        match self {
+            ProviderKind::Yahoo => Ok(Box::new(yahoo::YahooProvider::new())),
+            ProviderKind::Csv => {
+                let data = data.ok_or_else(|| {
+                    Error::Config("the csv provider requires --data <path>".to_string())
+                })?;
+                Ok(Box::new(csv_file::CsvProvider::open(data)?))
+            }
+        }
+    }
+
+    ///
+    /// Whether the provider fetches over the network, and is therefore worth caching.
+    ///
+    pub fn is_remote(&self) -> bool {
+        match self {
+            ProviderKind::Yahoo => true,
+            ProviderKind::Csv => false,
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderKind::Yahoo => write!(f, "yahoo"),
+            ProviderKind::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yahoo" => Ok(ProviderKind::Yahoo),
+            "csv" => Ok(ProviderKind::Csv),
+            _ => Err(format!("unknown provider '{}'", s)),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use super::{ProviderResult, QuoteProvider};
+use crate::events::adjust;
+use crate::model::{Bar, BarInterval, Event};
+use crate::resample::resample;
+use chrono::prelude::*;
+
+///
+/// Wraps another provider and replaces the adjusted closes it returns with ones computed from
+/// the traded closes and its dividends and splits, see `events::adjust`. Results are then the
+/// same whatever adjustment the provider applies, and reproducible from recorded events.
+///
+/// Weekly and monthly bars are resampled from the adjusted daily bars, as a bar stamped at the
+/// start of its week or month already closes after the events within it.
+///
+pub struct AdjustedProvider {
+    inner: Box<dyn QuoteProvider>,
+}
+
+impl AdjustedProvider {
+    pub fn new(inner: Box<dyn QuoteProvider>) -> Self {
+        AdjustedProvider { inner }
+    }
+}
+
+impl QuoteProvider for AdjustedProvider {
+    fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Bar>> {
+        self.fetch_bars(symbol, from, to, BarInterval::Day)
+    }
+
+    fn fetch_bars(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: BarInterval,
+    ) -> ProviderResult<Vec<Bar>> {
+        let events = self.inner.fetch_events(symbol, from, to)?;
+        if interval.is_intraday() {
+            let bars = self.inner.fetch_bars(symbol, from, to, interval)?;
+            return Ok(adjust(&bars, &events));
+        }
+        let daily = self.inner.fetch_history(symbol, from, to)?;
+        Ok(resample(&adjust(&daily, &events), interval))
+    }
+
+    fn fetch_events(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Event>> {
+        self.inner.fetch_events(symbol, from, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::EventKind;
+
+    ///
+    /// Daily closes from `from` on, with a 2:1 split `split` days later.
+    ///
+    struct Recorded {
+        closes: &'static [f64],
+        split: i64,
+    }
+
+    impl QuoteProvider for Recorded {
+        fn fetch_history(
+            &self,
+            _symbol: &str,
+            from: DateTime<Utc>,
+            _to: DateTime<Utc>,
+        ) -> ProviderResult<Vec<Bar>> {
+            Ok(self
+                .closes
+                .iter()
+                .enumerate()
+                .map(|(i, close)| Bar {
+                    timestamp: from + chrono::Duration::days(i as i64),
+                    open: *close,
+                    high: *close,
+                    low: *close,
+                    close: *close,
+                    adjclose: 1.0,
+                    volume: 0,
+                })
+                .collect())
+        }
+
+        fn fetch_events(
+            &self,
+            _symbol: &str,
+            from: DateTime<Utc>,
+            _to: DateTime<Utc>,
+        ) -> ProviderResult<Vec<Event>> {
+            Ok(vec![Event {
+                timestamp: from + chrono::Duration::days(self.split),
+                kind: EventKind::Split(2.0),
+            }])
+        }
+    }
+
+    #[test]
+    fn test_adjusted_provider() {
+        let provider = AdjustedProvider::new(Box::new(Recorded {
+            closes: &[20.0, 10.0],
+            split: 1,
+        }));
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let bars = provider.fetch_history("MSFT", from, from).unwrap();
+
+        let adjclose: Vec<f64> = bars.iter().map(|b| b.adjclose).collect();
+        assert_eq!(adjclose, vec![10.0, 10.0]);
+        assert_eq!(provider.fetch_events("MSFT", from, from).unwrap().len(), 1);
+        // the recorded provider only has daily bars
+        let hourly = provider.fetch_bars("MSFT", from, from, BarInterval::Hour);
+        assert!(matches!(hourly, Err(crate::error::Error::Config(_))));
+    }
+
+    #[test]
+    fn test_split_within_a_week() {
+        // Monday to Friday, split on Wednesday
+        let provider = AdjustedProvider::new(Box::new(Recorded {
+            closes: &[20.0, 22.0, 11.0, 10.0, 12.0],
+            split: 2,
+        }));
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let to = from + chrono::Duration::days(4);
+        let weekly = provider
+            .fetch_bars("MSFT", from, to, BarInterval::Week)
+            .unwrap();
+
+        let adjclose: Vec<f64> = weekly.iter().map(|b| b.adjclose).collect();
+        // the week closes after the split, so its close is not adjusted again
+        assert_eq!(adjclose, vec![12.0]);
+        assert_eq!(weekly[0].timestamp, from);
+    }
+}
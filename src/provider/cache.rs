@@ -0,0 +1,402 @@
+use super::csv_file::{read_bars, write_bars};
+use super::{ProviderResult, QuoteProvider};
+use crate::model::{Bar, BarInterval, Event};
+use crate::resample::resample;
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// Replaces `path` with what `write` writes, through a temporary file of its own so that
+/// neither an interrupted run nor a concurrent write of the same file leaves it truncated.
+///
+fn write_atomically<F>(path: &Path, write: F) -> ProviderResult<()>
+where
+    F: FnOnce(fs::File) -> ProviderResult<()>,
+{
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let tmp = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written = write(fs::File::create(&tmp)?).and_then(|_| Ok(fs::rename(&tmp, path)?));
+    if written.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    written
+}
+
+///
+/// How a `CachedProvider` treats the bars already on disk.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    /// Serve cached bars and only fetch the ranges that are missing
+    Incremental,
+    /// Ignore cached bars, fetch the full range again and overwrite the cache
+    Refresh,
+}
+
+///
+/// Wraps another provider and keeps the fetched bars in a directory of per-symbol files.
+///
+/// Each symbol is stored as `<SYMBOL>.csv` (readable by the csv provider) next to a
+/// `<SYMBOL>.range` file recording the period that has been fetched so far. Subsequent
+/// requests only hit the inner provider for the parts of the period not covered yet, plus
+/// the last cached bar, which may have been incomplete when it was fetched. If the fetched
+/// bars are adjusted differently from the cached ones they overlap with, the provider has
+/// revised the adjusted closes after a dividend or split, and the whole range is fetched again.
+///
+pub struct CachedProvider {
+    inner: Box<dyn QuoteProvider>,
+    dir: PathBuf,
+    mode: CacheMode,
+}
+
+impl CachedProvider {
+    pub fn new<P: AsRef<Path>>(inner: Box<dyn QuoteProvider>, dir: P, mode: CacheMode) -> Self {
+        CachedProvider {
+            inner,
+            dir: dir.as_ref().to_path_buf(),
+            mode,
+        }
+    }
+
+    fn paths(&self, symbol: &str) -> (PathBuf, PathBuf) {
+        let name = symbol.replace(['/', '\\'], "_");
+        (
+            self.dir.join(format!("{}.csv", name)),
+            self.dir.join(format!("{}.range", name)),
+        )
+    }
+
+    fn load(&self, symbol: &str) -> Option<(Vec<Bar>, DateTime<Utc>, DateTime<Utc>)> {
+        let (bars_path, range_path) = self.paths(symbol);
+        let range = fs::read_to_string(range_path).ok()?;
+        let mut lines = range.lines().map(DateTime::parse_from_rfc3339);
+        let from = lines.next()?.ok()?.with_timezone(&Utc);
+        let to = lines.next()?.ok()?.with_timezone(&Utc);
+        let bars = read_bars(fs::File::open(bars_path).ok()?).ok()?;
+
+        Some((bars, from, to))
+    }
+
+    fn store(
+        &self,
+        symbol: &str,
+        bars: &[Bar],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let (bars_path, range_path) = self.paths(symbol);
+
+        write_atomically(&bars_path, |file| write_bars(file, bars))?;
+        write_atomically(&range_path, |mut file| {
+            Ok(writeln!(
+                file,
+                "{}\n{}",
+                from.to_rfc3339(),
+                to.to_rfc3339()
+            )?)
+        })?;
+
+        Ok(())
+    }
+}
+
+///
+/// Merges `fresh` into `cached`, replacing bars with the same timestamp.
+///
+fn merge(cached: Vec<Bar>, fresh: Vec<Bar>) -> Vec<Bar> {
+    let merged: BTreeMap<DateTime<Utc>, Bar> = cached
+        .into_iter()
+        .chain(fresh)
+        .map(|b| (b.timestamp, b))
+        .collect();
+    merged.into_values().collect()
+}
+
+///
+/// Whether a bar of `fresh` has another adjustment factor than the cached bar of the same
+/// timestamp.
+///
+fn rebased(cached: &[Bar], fresh: &[Bar]) -> bool {
+    fresh.iter().any(
+        |bar| match cached.binary_search_by_key(&bar.timestamp, |b| b.timestamp) {
+            Ok(i) => {
+                let (before, now) = (cached[i].adjustment_factor(), bar.adjustment_factor());
+                (before - now).abs() > 1e-6 * now.abs()
+            }
+            Err(_) => false,
+        },
+    )
+}
+
+impl QuoteProvider for CachedProvider {
+    fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Bar>> {
+        let cached = match self.mode {
+            CacheMode::Incremental => self.load(symbol),
+            CacheMode::Refresh => None,
+        };
+
+        let (bars, covered_from, covered_to) = match cached {
+            Some((bars, cached_from, cached_to)) => {
+                let mut fresh = Vec::new();
+                if from < cached_from {
+                    fresh.extend(self.inner.fetch_history(symbol, from, cached_from)?);
+                }
+                if to > cached_to {
+                    let tail_start = bars
+                        .last()
+                        .map_or(cached_to, |b| b.timestamp.min(cached_to));
+                    fresh.extend(self.inner.fetch_history(symbol, tail_start, to)?);
+                }
+                let (covered_from, covered_to) = (from.min(cached_from), to.max(cached_to));
+                if rebased(&bars, &fresh) {
+                    let bars = self.inner.fetch_history(symbol, covered_from, covered_to)?;
+                    (bars, covered_from, covered_to)
+                } else {
+                    (merge(bars, fresh), covered_from, covered_to)
+                }
+            }
+            None => (self.inner.fetch_history(symbol, from, to)?, from, to),
+        };
+        self.store(symbol, &bars, covered_from, covered_to)?;
+
+        Ok(bars
+            .into_iter()
+            .filter(|b| b.timestamp >= from && b.timestamp <= to)
+            .collect())
+    }
+
+    /// Only daily bars are cached, coarser ones are resampled from them and intraday bars are
+    /// always fetched.
+    fn fetch_bars(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: BarInterval,
+    ) -> ProviderResult<Vec<Bar>> {
+        if interval.is_intraday() {
+            return self.inner.fetch_bars(symbol, from, to, interval);
+        }
+        Ok(resample(&self.fetch_history(symbol, from, to)?, interval))
+    }
+
+    /// Corporate actions are rare and revised after the fact, so they are not cached.
+    fn fetch_events(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Event>> {
+        self.inner.fetch_events(symbol, from, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    type Requests = Arc<Mutex<Vec<(DateTime<Utc>, DateTime<Utc>)>>>;
+
+    ///
+    /// Serves a daily bar at midnight of every day and records the ranges it is asked for. The
+    /// adjusted closes are the closes times `factor`.
+    ///
+    struct RecordingProvider {
+        requests: Requests,
+        factor: Arc<Mutex<f64>>,
+    }
+
+    impl QuoteProvider for RecordingProvider {
+        fn fetch_history(
+            &self,
+            _symbol: &str,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+        ) -> ProviderResult<Vec<Bar>> {
+            self.requests.lock().unwrap().push((from, to));
+            let mut t = Utc.from_utc_datetime(&from.date_naive().and_time(NaiveTime::MIN));
+            let mut bars = Vec::new();
+            while t <= to {
+                if t >= from {
+                    let price = t.ordinal() as f64;
+                    bars.push(Bar {
+                        timestamp: t,
+                        open: price,
+                        high: price,
+                        low: price,
+                        close: price,
+                        adjclose: price * *self.factor.lock().unwrap(),
+                        volume: 0,
+                    });
+                }
+                t += chrono::Duration::days(1);
+            }
+            Ok(bars)
+        }
+    }
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 1, d, 0, 0, 0).unwrap()
+    }
+
+    fn cached(name: &str, mode: CacheMode) -> (CachedProvider, Requests, PathBuf) {
+        let (provider, requests, _, dir) = cached_with_factor(name, mode);
+        (provider, requests, dir)
+    }
+
+    fn cached_with_factor(
+        name: &str,
+        mode: CacheMode,
+    ) -> (CachedProvider, Requests, Arc<Mutex<f64>>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "stock-tracker-cache-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let requests = Requests::default();
+        let factor = Arc::new(Mutex::new(1.0));
+        let inner = RecordingProvider {
+            requests: requests.clone(),
+            factor: factor.clone(),
+        };
+        (
+            CachedProvider::new(Box::new(inner), &dir, mode),
+            requests,
+            factor,
+            dir,
+        )
+    }
+
+    fn taken(requests: &Requests) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        std::mem::take(&mut *requests.lock().unwrap())
+    }
+
+    #[test]
+    fn test_incremental_fetches_missing_ranges() {
+        let (provider, requests, dir) = cached("incremental", CacheMode::Incremental);
+        let (_, range_path) = provider.paths("MSFT");
+
+        assert_eq!(
+            provider
+                .fetch_history("MSFT", day(5), day(10))
+                .unwrap()
+                .len(),
+            6
+        );
+        assert_eq!(taken(&requests), vec![(day(5), day(10))]);
+        assert_eq!(
+            fs::read_to_string(&range_path).unwrap(),
+            "2021-01-05T00:00:00+00:00\n2021-01-10T00:00:00+00:00\n"
+        );
+
+        // a covered period is served from the cache
+        assert_eq!(
+            provider
+                .fetch_history("MSFT", day(6), day(9))
+                .unwrap()
+                .len(),
+            4
+        );
+        assert!(taken(&requests).is_empty());
+
+        // the tail is fetched from the last cached bar, the head back to the cached start
+        let bars = provider.fetch_history("MSFT", day(3), day(12)).unwrap();
+        assert_eq!(bars.len(), 10);
+        assert_eq!(bars[0].timestamp, day(3));
+        assert_eq!(taken(&requests), vec![(day(3), day(5)), (day(10), day(12))]);
+        assert_eq!(
+            fs::read_to_string(&range_path).unwrap(),
+            "2021-01-03T00:00:00+00:00\n2021-01-12T00:00:00+00:00\n"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_revised_adjustment_fetches_everything() {
+        let (provider, requests, factor, dir) =
+            cached_with_factor("revised", CacheMode::Incremental);
+        provider.fetch_history("MSFT", day(5), day(10)).unwrap();
+        taken(&requests);
+
+        // a dividend went ex since, lowering the adjusted closes of all earlier bars
+        *factor.lock().unwrap() = 0.98;
+        let bars = provider.fetch_history("MSFT", day(5), day(12)).unwrap();
+        assert_eq!(
+            taken(&requests),
+            vec![(day(10), day(12)), (day(5), day(12))]
+        );
+        assert!(bars
+            .iter()
+            .all(|b| (b.adjustment_factor() - 0.98).abs() < 1e-9));
+        assert_eq!(provider.load("MSFT").unwrap().0, bars);
+
+        // unchanged factors keep the cache
+        provider.fetch_history("MSFT", day(5), day(13)).unwrap();
+        assert_eq!(taken(&requests), vec![(day(12), day(13))]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_fetches_everything() {
+        let (provider, requests, dir) = cached("refresh", CacheMode::Refresh);
+        provider.fetch_history("MSFT", day(5), day(10)).unwrap();
+        provider.fetch_history("MSFT", day(6), day(8)).unwrap();
+        assert_eq!(taken(&requests), vec![(day(5), day(10)), (day(6), day(8))]);
+
+        // the refreshed range replaces the one before
+        let (incremental, requests, _) = cached("refresh", CacheMode::Incremental);
+        assert_eq!(
+            incremental
+                .fetch_history("MSFT", day(6), day(8))
+                .unwrap()
+                .len(),
+            3
+        );
+        incremental.fetch_history("MSFT", day(5), day(8)).unwrap();
+        assert_eq!(taken(&requests), vec![(day(5), day(6))]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_fetches_of_a_symbol() {
+        let (provider, _, dir) = cached("concurrent", CacheMode::Incremental);
+        let provider = Arc::new(provider);
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let provider = provider.clone();
+                std::thread::spawn(move || provider.fetch_history("MSFT", day(1), day(20)))
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap().unwrap().len(), 20);
+        }
+
+        let mut files: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["MSFT.csv", "MSFT.range"]);
+        assert_eq!(provider.load("MSFT").unwrap().0.len(), 20);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
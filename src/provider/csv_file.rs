@@ -0,0 +1,384 @@
+use super::{ProviderResult, QuoteProvider};
+use crate::error::Error;
+use crate::model::{Bar, BarInterval, Event, EventKind};
+use crate::resample::resample;
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+///
+/// One row of a recorded quote file.
+///
+/// `symbol` is only required for single long-format files, `adjclose` falls back to `close`
+/// and a missing `volume` is treated as zero.
+///
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    #[serde(default)]
+    symbol: Option<String>,
+    timestamp: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    #[serde(default)]
+    adjclose: Option<f64>,
+    #[serde(default)]
+    volume: Option<u64>,
+}
+
+impl CsvRow {
+    fn into_bar(self) -> ProviderResult<Bar> {
+        Ok(Bar {
+            timestamp: parse_timestamp(&self.timestamp)?,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            adjclose: self.adjclose.unwrap_or(self.close),
+            volume: self.volume.unwrap_or_default(),
+        })
+    }
+}
+
+///
+/// Parses an RFC3339 timestamp, a plain `YYYY-MM-DD` date (midnight UTC) or unix seconds.
+///
+pub(crate) fn parse_timestamp(s: &str) -> ProviderResult<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        Ok(dt.with_timezone(&Utc))
+    } else if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)))
+    } else if let Some(dt) = s
+        .parse()
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+    {
+        Ok(dt)
+    } else {
+        Err(Error::Parse(format!("invalid timestamp '{}'", s)))
+    }
+}
+
+///
+/// One row of a recorded corporate action file. `value` is the amount per share of a dividend
+/// or the number of new shares per old share of a split.
+///
+#[derive(Debug, Deserialize)]
+struct EventRow {
+    timestamp: String,
+    #[serde(rename = "type")]
+    kind: String,
+    value: f64,
+}
+
+impl EventRow {
+    fn into_event(self) -> ProviderResult<Event> {
+        let kind = match self.kind.trim().to_ascii_lowercase().as_str() {
+            "dividend" => EventKind::Dividend(self.value),
+            "split" if self.value > 0.0 => EventKind::Split(self.value),
+            "split" => {
+                return Err(Error::Parse(format!(
+                    "invalid split ratio {} on {}",
+                    self.value, self.timestamp
+                )))
+            }
+            other => {
+                return Err(Error::Parse(format!(
+                    "unknown event type '{}', expected dividend or split",
+                    other
+                )))
+            }
+        };
+        Ok(Event {
+            timestamp: parse_timestamp(&self.timestamp)?,
+            kind,
+        })
+    }
+}
+
+///
+/// Reads corporate actions from a per-symbol event file.
+///
+pub(crate) fn read_events<R: Read>(reader: R) -> ProviderResult<Vec<Event>> {
+    let mut events = Vec::new();
+    for row in csv::Reader::from_reader(reader).deserialize::<EventRow>() {
+        events.push(row?.into_event()?);
+    }
+    Ok(events)
+}
+
+fn read_rows<R: Read>(reader: R) -> ProviderResult<Vec<CsvRow>> {
+    let mut rows = Vec::new();
+    for row in csv::Reader::from_reader(reader).deserialize() {
+        rows.push(row?);
+    }
+    Ok(rows)
+}
+
+///
+/// Reads bars from a per-symbol quote file.
+///
+pub(crate) fn read_bars<R: Read>(reader: R) -> ProviderResult<Vec<Bar>> {
+    read_rows(reader)?
+        .into_iter()
+        .map(CsvRow::into_bar)
+        .collect()
+}
+
+///
+/// Writes bars in the per-symbol format understood by `read_bars`.
+///
+pub(crate) fn write_bars<W: Write>(writer: W, bars: &[Bar]) -> ProviderResult<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "timestamp",
+        "open",
+        "high",
+        "low",
+        "close",
+        "adjclose",
+        "volume",
+    ])?;
+    for bar in bars {
+        writer.write_record([
+            bar.timestamp.to_rfc3339(),
+            bar.open.to_string(),
+            bar.high.to_string(),
+            bar.low.to_string(),
+            bar.close.to_string(),
+            bar.adjclose.to_string(),
+            bar.volume.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+enum Source {
+    /// A directory holding one `<SYMBOL>.csv` file per symbol
+    Directory(PathBuf),
+    /// A single long-format file with a `symbol` column, loaded up front
+    LongFormat(HashMap<String, Vec<Bar>>),
+}
+
+///
+/// Serves quotes from recorded CSV files instead of a live API.
+///
+/// Files need a header row with the columns `timestamp,open,high,low,close` and optionally
+/// `adjclose` and `volume`. In a directory, corporate actions are read from an optional
+/// `<SYMBOL>.events.csv` file with the columns `timestamp,type,value`.
+///
+/// Recorded bars may be of any interval and are resampled to coarser ones on request. Asking
+/// for an intraday interval finer than the recorded bars is an `Error::Config`.
+///
+pub struct CsvProvider {
+    source: Source,
+}
+
+impl CsvProvider {
+    ///
+    /// Opens `path` as a directory of per-symbol files or as a single long-format file.
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> ProviderResult<Self> {
+        let path = path.as_ref();
+        let source = if path.is_dir() {
+            Source::Directory(path.to_path_buf())
+        } else {
+            let mut bars: HashMap<String, Vec<Bar>> = HashMap::new();
+            for row in read_rows(std::fs::File::open(path)?)? {
+                let symbol = row.symbol.clone().ok_or_else(|| {
+                    Error::Parse(format!("missing 'symbol' column in {}", path.display()))
+                })?;
+                bars.entry(symbol).or_default().push(row.into_bar()?);
+            }
+            Source::LongFormat(bars)
+        };
+        Ok(CsvProvider { source })
+    }
+
+    fn load(&self, symbol: &str) -> ProviderResult<Vec<Bar>> {
+        match &self.source {
+            Source::Directory(dir) => {
+                let path = dir.join(format!("{}.csv", symbol));
+                if !path.is_file() {
+                    return Err(Error::UnknownSymbol(symbol.to_string()));
+                }
+                read_bars(std::fs::File::open(path)?)
+            }
+            Source::LongFormat(bars) => bars
+                .get(symbol)
+                .cloned()
+                .ok_or_else(|| Error::UnknownSymbol(symbol.to_string())),
+        }
+    }
+}
+
+impl QuoteProvider for CsvProvider {
+    fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Bar>> {
+        let mut bars: Vec<Bar> = self
+            .load(symbol)?
+            .into_iter()
+            .filter(|b| b.timestamp >= from && b.timestamp <= to)
+            .collect();
+        bars.sort_by_key(|b| b.timestamp);
+
+        Ok(bars)
+    }
+
+    fn fetch_bars(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: BarInterval,
+    ) -> ProviderResult<Vec<Bar>> {
+        let bars = self.fetch_history(symbol, from, to)?;
+        if let Some(length) = intraday_length(interval) {
+            let spacing = bars
+                .windows(2)
+                .map(|w| w[1].timestamp - w[0].timestamp)
+                .min();
+            if matches!(spacing, Some(spacing) if spacing > length) {
+                return Err(Error::Config(format!(
+                    "the recorded bars of {} are coarser than {}",
+                    symbol, interval
+                )));
+            }
+        }
+        Ok(resample(&bars, interval))
+    }
+
+    fn fetch_events(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Event>> {
+        let path = match &self.source {
+            Source::Directory(dir) => dir.join(format!("{}.events.csv", symbol)),
+            Source::LongFormat(_) => return Ok(Vec::new()),
+        };
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let mut events: Vec<Event> = read_events(std::fs::File::open(path)?)?
+            .into_iter()
+            .filter(|e| e.timestamp >= from && e.timestamp <= to)
+            .collect();
+        events.sort_by_key(|e| e.timestamp);
+
+        Ok(events)
+    }
+}
+
+///
+/// The length of an intraday `interval`, `None` for daily and longer intervals.
+///
+fn intraday_length(interval: BarInterval) -> Option<Duration> {
+    match interval {
+        BarInterval::Minute => Some(Duration::minutes(1)),
+        BarInterval::FiveMinutes => Some(Duration::minutes(5)),
+        BarInterval::Hour => Some(Duration::hours(1)),
+        BarInterval::Day | BarInterval::Week | BarInterval::Month => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        assert_eq!(parse_timestamp("2021-01-04T00:00:00Z").unwrap(), expected);
+        assert_eq!(
+            parse_timestamp("2021-01-04T01:00:00+01:00").unwrap(),
+            expected
+        );
+        assert_eq!(parse_timestamp("2021-01-04").unwrap(), expected);
+        assert_eq!(parse_timestamp("1609718400").unwrap(), expected);
+        assert!(matches!(parse_timestamp("yesterday"), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn test_directory_provider() {
+        let dir = std::env::temp_dir().join(format!("stock-tracker-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("MSFT.csv"),
+            "timestamp,open,high,low,close,volume\n\
+             2021-01-05,2,2,2,2,20\n\
+             2021-01-04,1,1,1,1,10\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("SPY.csv"),
+            "timestamp,open,high,low,close,volume\n\
+             1609754400,1,1,1,1,10\n\
+             1609754460,2,2,2,2,20\n\
+             1609754700,3,3,3,3,30\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("MSFT.events.csv"),
+            "timestamp,type,value\n\
+             2021-01-05,split,2\n\
+             2021-01-04,dividend,0.56\n\
+             2020-12-01,dividend,0.56\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::open(&dir).unwrap();
+        let from = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap();
+
+        let bars = provider.fetch_history("MSFT", from, to).unwrap();
+        let unknown = provider.fetch_history("IBM", from, to);
+        let weekly = provider
+            .fetch_bars("MSFT", from, to, BarInterval::Week)
+            .unwrap();
+        let hourly = provider.fetch_bars("MSFT", from, to, BarInterval::Hour);
+        let five_minutes = provider
+            .fetch_bars("SPY", from, to, BarInterval::FiveMinutes)
+            .unwrap();
+        let events = provider.fetch_events("MSFT", from, to).unwrap();
+        let no_events = provider.fetch_events("IBM", from, to).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let closes: Vec<f64> = bars.iter().map(|b| b.adjclose).collect();
+        assert_eq!(closes, vec![1.0, 2.0]);
+        assert_eq!(bars[1].volume, 20);
+        assert_eq!(weekly.len(), 1);
+        assert_eq!((weekly[0].close, weekly[0].volume), (2.0, 30));
+        assert!(matches!(hourly, Err(Error::Config(_))));
+        let volumes: Vec<u64> = five_minutes.iter().map(|b| b.volume).collect();
+        assert_eq!(volumes, vec![30, 30]);
+        assert!(matches!(unknown, Err(Error::UnknownSymbol(s)) if s == "IBM"));
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![EventKind::Dividend(0.56), EventKind::Split(2.0)]
+        );
+        assert!(no_events.is_empty());
+    }
+
+    #[test]
+    fn test_read_events() {
+        let invalid = [
+            "timestamp,type,value\n2021-01-04,spinoff,1\n",
+            "timestamp,type,value\n2021-01-04,split,0\n",
+        ];
+        for data in invalid.iter() {
+            assert!(matches!(read_events(data.as_bytes()), Err(Error::Parse(_))));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use super::{ProviderResult, QuoteProvider};
+use crate::error::Error;
+use crate::model::{Bar, BarInterval, Event, EventKind};
+use chrono::prelude::*;
+use yahoo_finance_api as yahoo;
+
+///
+/// Fetches quotes from the yahoo! finance API, which offers every `BarInterval` itself.
+///
+pub struct YahooProvider {
+    connector: yahoo::YahooConnector,
+}
+
+impl YahooProvider {
+    pub fn new() -> Self {
+        YahooProvider {
+            connector: yahoo::YahooConnector::new(),
+        }
+    }
+}
+
+impl Default for YahooProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Converts yahoo's errors, using `symbol` for the errors that refer to it.
+///
+fn with_symbol(e: yahoo::YahooError, symbol: &str) -> Error {
+    match e {
+        // yahoo answers requests for unknown tickers with "404 Not Found"
+        yahoo::YahooError::FetchFailed(ref status) if status.contains("404") => {
+            Error::UnknownSymbol(symbol.to_string())
+        }
+        yahoo::YahooError::EmptyDataSet => Error::EmptyRange(symbol.to_string()),
+        e => e.into(),
+    }
+}
+
+impl QuoteProvider for YahooProvider {
+    fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Bar>> {
+        self.fetch_bars(symbol, from, to, BarInterval::Day)
+    }
+
+    fn fetch_bars(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: BarInterval,
+    ) -> ProviderResult<Vec<Bar>> {
+        let response = self
+            .connector
+            .get_quote_history_interval(symbol, from, to, &interval.to_string())
+            .map_err(|e| with_symbol(e, symbol))?;
+        let mut bars: Vec<Bar> = response
+            .quotes()
+            .map_err(|e| with_symbol(e, symbol))?
+            .into_iter()
+            .filter_map(|q| {
+                Some(Bar {
+                    timestamp: Utc.timestamp_opt(q.timestamp as i64, 0).single()?,
+                    open: q.open,
+                    high: q.high,
+                    low: q.low,
+                    close: q.close,
+                    adjclose: q.adjclose,
+                    volume: q.volume,
+                })
+            })
+            .collect();
+        bars.sort_by_key(|b| b.timestamp);
+
+        Ok(bars)
+    }
+
+    fn fetch_events(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> ProviderResult<Vec<Event>> {
+        let response = self
+            .connector
+            .get_quote_history(symbol, from, to)
+            .map_err(|e| with_symbol(e, symbol))?;
+        let timestamp = |date: u64| Utc.timestamp_opt(date as i64, 0).single();
+
+        let dividends = response
+            .dividends()
+            .map_err(|e| with_symbol(e, symbol))?
+            .into_iter()
+            .filter_map(|d| {
+                Some(Event {
+                    timestamp: timestamp(d.date)?,
+                    kind: EventKind::Dividend(d.amount),
+                })
+            });
+        // yahoo reports a 4:1 split as numerator 4 and denominator 1
+        let splits = response
+            .splits()
+            .map_err(|e| with_symbol(e, symbol))?
+            .into_iter()
+            .filter(|s| s.numerator > 0 && s.denominator > 0)
+            .filter_map(|s| {
+                Some(Event {
+                    timestamp: timestamp(s.date)?,
+                    kind: EventKind::Split(s.numerator as f64 / s.denominator as f64),
+                })
+            });
+        let mut events: Vec<Event> = dividends.chain(splits).collect();
+        events.sort_by_key(|e| e.timestamp);
+
+        Ok(events)
+    }
+}
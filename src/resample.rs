@@ -0,0 +1,126 @@
+//!
+//! Aggregates fine bars into coarser ones, e.g. daily bars into weekly or monthly bars.
+//!
+
+use crate::model::{Bar, BarInterval};
+use chrono::prelude::*;
+use chrono::Duration;
+
+///
+/// The start of the bar of `interval` that `timestamp` falls into. Weeks start on Monday and
+/// all buckets are aligned in UTC.
+///
+pub fn bucket_start(timestamp: DateTime<Utc>, interval: BarInterval) -> DateTime<Utc> {
+    let midnight = |date: NaiveDate| Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+    let date = timestamp.date_naive();
+    let minutes = |n: i64| {
+        let seconds = timestamp.timestamp();
+        let start = seconds - seconds.rem_euclid(n * 60);
+        Utc.timestamp_opt(start, 0).single().unwrap_or(timestamp)
+    };
+    match interval {
+        BarInterval::Minute => minutes(1),
+        BarInterval::FiveMinutes => minutes(5),
+        BarInterval::Hour => minutes(60),
+        BarInterval::Day => midnight(date),
+        BarInterval::Week => {
+            midnight(date - Duration::days(date.weekday().num_days_from_monday() as i64))
+        }
+        BarInterval::Month => midnight(date.with_day(1).expect("every month has a first day")),
+    }
+}
+
+///
+/// Aggregates `bars`, sorted by timestamp, into bars of `interval`: the open of the first bar,
+/// the highest high, the lowest low, the closes of the last bar and the summed volume. Each
+/// aggregated bar keeps the timestamp of its first bar, like the weekly and monthly bars of
+/// most providers. Bars that are already as coarse as `interval` are returned unchanged.
+///
+pub fn resample(bars: &[Bar], interval: BarInterval) -> Vec<Bar> {
+    let mut resampled: Vec<Bar> = Vec::new();
+    let mut current = None;
+    for bar in bars {
+        let bucket = bucket_start(bar.timestamp, interval);
+        match resampled.last_mut() {
+            Some(last) if current == Some(bucket) => {
+                last.high = last.high.max(bar.high);
+                last.low = last.low.min(bar.low);
+                last.close = bar.close;
+                last.adjclose = bar.adjclose;
+                last.volume += bar.volume;
+            }
+            _ => {
+                resampled.push(bar.clone());
+                current = Some(bucket);
+            }
+        }
+    }
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(timestamp: DateTime<Utc>, open: f64, close: f64) -> Bar {
+        Bar {
+            timestamp,
+            open,
+            high: open.max(close) + 1.0,
+            low: open.min(close) - 1.0,
+            close,
+            adjclose: close / 2.0,
+            volume: 10,
+        }
+    }
+
+    fn day(month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, month, day, 14, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_bucket_start() {
+        let t = Utc.with_ymd_and_hms(2021, 1, 7, 14, 37, 12).unwrap();
+        let at = |d, h, m| Utc.with_ymd_and_hms(2021, 1, d, h, m, 0).unwrap();
+        assert_eq!(bucket_start(t, BarInterval::Minute), at(7, 14, 37));
+        assert_eq!(bucket_start(t, BarInterval::FiveMinutes), at(7, 14, 35));
+        assert_eq!(bucket_start(t, BarInterval::Hour), at(7, 14, 0));
+        assert_eq!(bucket_start(t, BarInterval::Day), at(7, 0, 0));
+        // the 7th is a Thursday
+        assert_eq!(bucket_start(t, BarInterval::Week), at(4, 0, 0));
+        assert_eq!(bucket_start(t, BarInterval::Month), at(1, 0, 0));
+    }
+
+    #[test]
+    fn test_resample() {
+        let bars = vec![
+            bar(day(1, 28), 10.0, 11.0),
+            bar(day(1, 29), 11.0, 12.0),
+            // a new week and month
+            bar(day(2, 1), 12.0, 9.0),
+            bar(day(2, 2), 9.0, 10.0),
+            bar(day(2, 8), 10.0, 15.0),
+        ];
+
+        let weekly = resample(&bars, BarInterval::Week);
+        assert_eq!(weekly.len(), 3);
+        assert_eq!(weekly[1].timestamp, day(2, 1));
+        assert_eq!(
+            (
+                weekly[1].open,
+                weekly[1].high,
+                weekly[1].low,
+                weekly[1].close
+            ),
+            (12.0, 13.0, 8.0, 10.0)
+        );
+        assert_eq!(weekly[1].adjclose, 5.0);
+        assert_eq!(weekly[1].volume, 20);
+
+        let monthly = resample(&bars, BarInterval::Month);
+        let closes: Vec<f64> = monthly.iter().map(|b| b.close).collect();
+        assert_eq!(closes, vec![12.0, 15.0]);
+        assert_eq!(resample(&bars, BarInterval::Day), bars);
+        assert!(resample(&[], BarInterval::Week).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+//!
+//! Path dependent risk metrics of a price series: volatility, drawdown, risk adjusted returns
+//! and value at risk.
+//!
+
+use crate::model::Bar;
+use crate::output::{Field, Record};
+use chrono::prelude::*;
+
+///
+/// The assumptions behind the risk metrics.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiskOptions {
+    /// The annual risk-free rate as a fraction, e.g. 0.04 for 4%
+    pub risk_free_rate: f64,
+    /// The confidence level of the value at risk as a fraction, e.g. 0.95
+    pub confidence: f64,
+    /// The number of bars in a year, used to annualize per-bar figures
+    pub periods_per_year: f64,
+}
+
+impl Default for RiskOptions {
+    fn default() -> Self {
+        RiskOptions {
+            risk_free_rate: 0.0,
+            confidence: 0.95,
+            // trading days
+            periods_per_year: 252.0,
+        }
+    }
+}
+
+///
+/// The largest peak to trough decline of a series.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drawdown {
+    /// The decline relative to the peak as a fraction, 0 if the series never declined
+    pub depth: f64,
+    /// The index of the peak
+    pub peak: usize,
+    /// The index of the trough
+    pub trough: usize,
+}
+
+///
+/// Calculate the logarithmic returns between consecutive values.
+///
+pub fn log_returns(series: &[f64]) -> Vec<f64> {
+    series.windows(2).map(|w| (w[1] / w[0]).ln()).collect()
+}
+
+///
+/// Calculate the relative returns between consecutive values.
+///
+pub fn simple_returns(series: &[f64]) -> Vec<f64> {
+    series.windows(2).map(|w| w[1] / w[0] - 1.0).collect()
+}
+
+pub(crate) fn mean(series: &[f64]) -> Option<f64> {
+    if series.is_empty() {
+        None
+    } else {
+        Some(series.iter().sum::<f64>() / series.len() as f64)
+    }
+}
+
+///
+/// Calculate the sample standard deviation, which needs at least two values.
+///
+pub fn stdev(series: &[f64]) -> Option<f64> {
+    if series.len() < 2 {
+        return None;
+    }
+    let mean = mean(series)?;
+    let variance =
+        series.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (series.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+///
+/// Calculate the annualized volatility: the standard deviation of the log returns of `series`
+/// scaled by the square root of `periods_per_year`.
+///
+pub fn annualized_volatility(series: &[f64], periods_per_year: f64) -> Option<f64> {
+    Some(stdev(&log_returns(series))? * periods_per_year.sqrt())
+}
+
+///
+/// Find the largest decline of `series` from a previous peak.
+///
+pub fn max_drawdown(series: &[f64]) -> Option<Drawdown> {
+    if series.is_empty() {
+        return None;
+    }
+
+    let mut peak = 0;
+    let mut drawdown = Drawdown {
+        depth: 0.0,
+        peak: 0,
+        trough: 0,
+    };
+    for (i, value) in series.iter().enumerate() {
+        if *value > series[peak] {
+            peak = i;
+        }
+        let depth = 1.0 - value / series[peak];
+        if depth > drawdown.depth {
+            drawdown = Drawdown {
+                depth,
+                peak,
+                trough: i,
+            };
+        }
+    }
+    Some(drawdown)
+}
+
+///
+/// Calculate the annualized Sharpe ratio of per-bar `returns` against an annual risk-free rate.
+///
+pub fn sharpe_ratio(returns: &[f64], risk_free_rate: f64, periods_per_year: f64) -> Option<f64> {
+    let excess: Vec<f64> = returns
+        .iter()
+        .map(|r| r - risk_free_rate / periods_per_year)
+        .collect();
+    let deviation = stdev(returns)?;
+    if deviation == 0.0 {
+        return None;
+    }
+    Some(mean(&excess)? / deviation * periods_per_year.sqrt())
+}
+
+///
+/// Calculate the annualized Sortino ratio of per-bar `returns` against an annual risk-free
+/// rate. Unlike the Sharpe ratio only returns below the risk-free rate count as risk.
+///
+pub fn sortino_ratio(returns: &[f64], risk_free_rate: f64, periods_per_year: f64) -> Option<f64> {
+    let excess: Vec<f64> = returns
+        .iter()
+        .map(|r| r - risk_free_rate / periods_per_year)
+        .collect();
+    let downside: Vec<f64> = excess.iter().map(|r| r.min(0.0).powi(2)).collect();
+    let deviation = mean(&downside)?.sqrt();
+    if deviation == 0.0 {
+        return None;
+    }
+    Some(mean(&excess)? / deviation * periods_per_year.sqrt())
+}
+
+///
+/// Calculate the historical value at risk: the loss per bar that was not exceeded with the
+/// given `confidence`, interpolating linearly between the observed returns.
+///
+/// # Returns
+/// The loss as a positive fraction.
+///
+pub fn historical_var(returns: &[f64], confidence: f64) -> Option<f64> {
+    if returns.is_empty() || !(0.0..1.0).contains(&confidence) {
+        return None;
+    }
+
+    let mut sorted = returns.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let position = (1.0 - confidence) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let quantile = sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64);
+    Some(-quantile)
+}
+
+///
+/// Calculate the parametric value at risk, assuming normally distributed returns.
+///
+/// # Returns
+/// The loss as a positive fraction.
+///
+pub fn parametric_var(returns: &[f64], confidence: f64) -> Option<f64> {
+    if !(0.0..1.0).contains(&confidence) || confidence == 0.0 {
+        return None;
+    }
+    Some(-(mean(returns)? + inverse_normal_cdf(1.0 - confidence) * stdev(returns)?))
+}
+
+///
+/// The quantile function of the standard normal distribution for 0 < `p` < 1, using Acklam's
+/// rational approximation with a relative error below 1.15e-9.
+///
+fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+///
+/// The risk metrics of a symbol over the summarized period. Metrics that need more bars than
+/// available are `None`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskMetrics {
+    /// The annualized volatility of the log returns as a fraction
+    pub volatility: Option<f64>,
+    /// The depth of the maximum drawdown as a fraction
+    pub max_drawdown: f64,
+    pub drawdown_peak: DateTime<Utc>,
+    pub drawdown_trough: DateTime<Utc>,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    /// The historical value at risk of a single bar as a fraction
+    pub historical_var: Option<f64>,
+    /// The parametric value at risk of a single bar as a fraction
+    pub parametric_var: Option<f64>,
+}
+
+impl RiskMetrics {
+    ///
+    /// Calculates the risk metrics of the adjusted closes of `bars`, which have to be sorted by
+    /// timestamp.
+    ///
+    /// # Returns
+    /// `None` if there are no bars.
+    ///
+    pub fn from_bars(bars: &[Bar], options: &RiskOptions) -> Option<RiskMetrics> {
+        let closes: Vec<f64> = bars.iter().map(|b| b.adjclose).collect();
+        let returns = simple_returns(&closes);
+        let drawdown = max_drawdown(&closes)?;
+
+        Some(RiskMetrics {
+            volatility: annualized_volatility(&closes, options.periods_per_year),
+            max_drawdown: drawdown.depth,
+            drawdown_peak: bars[drawdown.peak].timestamp,
+            drawdown_trough: bars[drawdown.trough].timestamp,
+            sharpe: sharpe_ratio(&returns, options.risk_free_rate, options.periods_per_year),
+            sortino: sortino_ratio(&returns, options.risk_free_rate, options.periods_per_year),
+            historical_var: historical_var(&returns, options.confidence),
+            parametric_var: parametric_var(&returns, options.confidence),
+        })
+    }
+}
+
+impl Record for RiskMetrics {
+    fn fields(&self) -> Vec<(String, Field)> {
+        let pct = |value: Option<f64>| Field::Number(value.map(|v| v * 100.0));
+        vec![
+            ("volatility_pct".to_string(), pct(self.volatility)),
+            ("max_drawdown_pct".to_string(), pct(Some(self.max_drawdown))),
+            (
+                "drawdown_peak".to_string(),
+                Field::Timestamp(self.drawdown_peak),
+            ),
+            (
+                "drawdown_trough".to_string(),
+                Field::Timestamp(self.drawdown_trough),
+            ),
+            ("sharpe".to_string(), Field::Number(self.sharpe)),
+            ("sortino".to_string(), Field::Number(self.sortino)),
+            ("var_historical_pct".to_string(), pct(self.historical_var)),
+            ("var_parametric_pct".to_string(), pct(self.parametric_var)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_annualized_volatility() {
+        let series = [100.0, 110.0, 99.0, 108.9];
+        assert_close(annualized_volatility(&series, 252.0), 1.8391773034294787);
+        assert_eq!(annualized_volatility(&[100.0, 110.0], 252.0), None);
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        let series = [10.0, 12.0, 9.0, 11.0, 13.0, 10.4, 12.0];
+        assert_eq!(
+            max_drawdown(&series),
+            Some(Drawdown {
+                depth: 0.25,
+                peak: 1,
+                trough: 2
+            })
+        );
+        assert_eq!(max_drawdown(&[1.0, 2.0, 3.0]).unwrap().depth, 0.0);
+        assert_eq!(max_drawdown(&[]), None);
+    }
+
+    #[test]
+    fn test_sharpe_and_sortino() {
+        let returns = [0.01, -0.02, 0.03, 0.005, -0.01];
+        assert_close(sharpe_ratio(&returns, 0.0, 252.0), 2.475829099459357);
+        assert_close(sharpe_ratio(&returns, 0.0252, 252.0), 2.393301462810712);
+        assert_close(sortino_ratio(&returns, 0.0, 252.0), 4.762352359916262);
+        assert_eq!(sortino_ratio(&[0.01, 0.02], 0.0, 252.0), None);
+        assert_eq!(sharpe_ratio(&[0.01], 0.0, 252.0), None);
+    }
+
+    #[test]
+    fn test_value_at_risk() {
+        let returns = [0.01, -0.02, 0.03, 0.005, -0.01];
+        assert_close(historical_var(&returns, 0.95), 0.018);
+        assert_close(historical_var(&returns, 0.75), 0.01);
+        assert_close(parametric_var(&returns, 0.95), 0.028639391239644655);
+        assert_eq!(historical_var(&[], 0.95), None);
+        assert_eq!(parametric_var(&returns, 1.0), None);
+    }
+
+    #[test]
+    fn test_inverse_normal_cdf() {
+        assert!(inverse_normal_cdf(0.5).abs() < 1e-9);
+        assert!((inverse_normal_cdf(0.05) + 1.6448536269514726).abs() < 1e-8);
+        assert!((inverse_normal_cdf(0.001) + 3.090232306167813).abs() < 1e-8);
+        assert!((inverse_normal_cdf(0.999) - 3.090232306167813).abs() < 1e-8);
+    }
+}
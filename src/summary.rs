@@ -0,0 +1,325 @@
+//!
+//! The per-symbol summary row.
+//!
+
+use crate::benchmark::BenchmarkMetrics;
+use crate::events::DividendMetrics;
+use crate::indicators::{max, min, price_diff, Indicator, Window};
+use crate::model::{Bar, Event, PriceField};
+use crate::output::{Field, Record};
+use crate::risk::{RiskMetrics, RiskOptions};
+use chrono::prelude::*;
+
+///
+/// Which indicator columns to compute for every summary, and from which price.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryOptions {
+    pub indicators: Vec<Indicator>,
+    pub price_field: PriceField,
+    pub risk: RiskOptions,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        SummaryOptions {
+            indicators: vec![Indicator::Sma(Window::Bars(30))],
+            price_field: PriceField::Adjclose,
+            risk: RiskOptions::default(),
+        }
+    }
+}
+
+///
+/// The summary row printed for every symbol.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// The timestamp of the first bar, where the period actually starts
+    pub period_start: DateTime<Utc>,
+    pub symbol: String,
+    pub price: f64,
+    /// The relative change over the period in percent
+    pub change_pct: f64,
+    /// The lowest and highest adjusted close
+    pub min: f64,
+    pub max: f64,
+    /// The lowest low and highest high of the period, adjusted like the close
+    pub low: f64,
+    pub high: f64,
+    pub risk: RiskMetrics,
+    /// The metrics against the benchmark, if one was compared with `compare_to`
+    pub benchmark: Option<BenchmarkMetrics>,
+    /// The dividend yield and total return, if events were added with `add_events`
+    pub dividends: Option<DividendMetrics>,
+    /// The last value of every requested indicator, named after its column. `None` if there
+    /// is not enough data.
+    pub indicators: Vec<(String, Option<f64>)>,
+}
+
+impl Summary {
+    ///
+    /// Summarizes the prices of `bars` selected by the options, see `Bar::priced_by`. The bars
+    /// have to be sorted by timestamp.
+    ///
+    /// # Returns
+    /// `None` if there are no bars.
+    ///
+    pub fn from_bars(symbol: &str, bars: &[Bar], options: &SummaryOptions) -> Option<Summary> {
+        let bars = priced(bars, options.price_field);
+        let bars = bars.as_slice();
+        let closes: Vec<f64> = bars.iter().map(|b| b.adjclose).collect();
+        let (_, pct_change) = price_diff(&closes)?;
+        let adjusted: Vec<Bar> = bars.iter().map(Bar::adjusted).collect();
+        let lows: Vec<f64> = adjusted.iter().map(|b| b.low).collect();
+        let highs: Vec<f64> = adjusted.iter().map(|b| b.high).collect();
+
+        let indicators = options
+            .indicators
+            .iter()
+            .flat_map(|indicator| indicator.compute(bars))
+            .map(|(name, values)| (name, values.last().copied().flatten()))
+            .collect();
+
+        Some(Summary {
+            period_start: bars.first()?.timestamp,
+            symbol: symbol.to_string(),
+            price: *closes.last()?,
+            change_pct: pct_change * 100.0,
+            min: min(&closes)?,
+            max: max(&closes)?,
+            low: min(&lows)?,
+            high: max(&highs)?,
+            risk: RiskMetrics::from_bars(bars, &options.risk)?,
+            benchmark: None,
+            dividends: None,
+            indicators,
+        })
+    }
+
+    ///
+    /// Adds the metrics of `bars`, the bars this summary was made from, against the bars of a
+    /// benchmark.
+    ///
+    pub fn compare_to(&mut self, bars: &[Bar], benchmark: &[Bar], options: &SummaryOptions) {
+        self.benchmark = Some(BenchmarkMetrics::from_bars(
+            &priced(bars, options.price_field),
+            &priced(benchmark, options.price_field),
+            &options.risk,
+        ));
+    }
+
+    ///
+    /// Adds the dividend metrics of `bars`, the bars this summary was made from, from the
+    /// corporate actions of the symbol.
+    ///
+    pub fn add_events(&mut self, bars: &[Bar], events: &[Event]) {
+        self.dividends = DividendMetrics::from_bars(bars, events);
+    }
+}
+
+fn priced(bars: &[Bar], field: PriceField) -> Vec<Bar> {
+    bars.iter().map(|b| b.priced_by(field)).collect()
+}
+
+impl Record for Summary {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            (
+                "period_start".to_string(),
+                Field::Timestamp(self.period_start),
+            ),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("price".to_string(), Field::Number(Some(self.price))),
+            (
+                "change_pct".to_string(),
+                Field::Number(Some(self.change_pct)),
+            ),
+            ("min".to_string(), Field::Number(Some(self.min))),
+            ("max".to_string(), Field::Number(Some(self.max))),
+            ("low".to_string(), Field::Number(Some(self.low))),
+            ("high".to_string(), Field::Number(Some(self.high))),
+        ]
+        .into_iter()
+        .chain(self.risk.fields())
+        .chain(self.benchmark.iter().flat_map(|b| b.fields()))
+        .chain(self.dividends.iter().flat_map(|d| d.fields()))
+        .chain(
+            self.indicators
+                .iter()
+                .map(|(name, value)| (name.clone(), Field::Number(*value))),
+        )
+        .collect()
+    }
+}
+
+///
+/// A single bar with the value of every requested indicator at that bar.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesRow {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub price: f64,
+    pub indicators: Vec<(String, Option<f64>)>,
+}
+
+///
+/// Computes the full indicator series for `bars`, which have to be sorted by timestamp.
+///
+pub fn series_rows(symbol: &str, bars: &[Bar], options: &SummaryOptions) -> Vec<SeriesRow> {
+    let bars = priced(bars, options.price_field);
+    let bars = bars.as_slice();
+    let columns: Vec<(String, Vec<Option<f64>>)> = options
+        .indicators
+        .iter()
+        .flat_map(|indicator| indicator.compute(bars))
+        .collect();
+
+    bars.iter()
+        .enumerate()
+        .map(|(i, bar)| SeriesRow {
+            timestamp: bar.timestamp,
+            symbol: symbol.to_string(),
+            price: bar.adjclose,
+            indicators: columns
+                .iter()
+                .map(|(name, values)| (name.clone(), values[i]))
+                .collect(),
+        })
+        .collect()
+}
+
+impl Record for SeriesRow {
+    fn fields(&self) -> Vec<(String, Field)> {
+        vec![
+            ("timestamp".to_string(), Field::Timestamp(self.timestamp)),
+            ("symbol".to_string(), Field::Text(self.symbol.clone())),
+            ("price".to_string(), Field::Number(Some(self.price))),
+        ]
+        .into_iter()
+        .chain(
+            self.indicators
+                .iter()
+                .map(|(name, value)| (name.clone(), Field::Number(*value))),
+        )
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(day: u32, adjclose: f64) -> Bar {
+        Bar {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap(),
+            open: adjclose,
+            high: adjclose,
+            low: adjclose,
+            close: adjclose,
+            adjclose,
+            volume: 0,
+        }
+    }
+
+    #[test]
+    fn test_summary_from_bars() {
+        let mut bars = vec![bar(1, 10.0), bar(2, 8.0), bar(3, 12.5)];
+        // a 2:1 split after the first bar: its traded prices are twice the adjusted ones
+        bars[0].high = 22.0;
+        bars[0].low = 18.0;
+        bars[0].close = 20.0;
+        bars[2].high = 13.0;
+        let options = SummaryOptions {
+            indicators: vec![
+                Indicator::Sma(Window::Bars(2)),
+                Indicator::Sma(Window::Bars(30)),
+                "sma:1d".parse().unwrap(),
+            ],
+            ..SummaryOptions::default()
+        };
+        let summary = Summary::from_bars("MSFT", &bars, &options).unwrap();
+
+        // the first bar, not the requested start of the period
+        assert_eq!(summary.period_start, bars[0].timestamp);
+        assert_eq!(summary.price, 12.5);
+        assert_eq!(summary.change_pct, 25.0);
+        assert_eq!(summary.min, 8.0);
+        assert_eq!(summary.max, 12.5);
+        assert_eq!(summary.low, 8.0);
+        assert_eq!(summary.high, 13.0);
+        assert!((summary.risk.max_drawdown - 0.2).abs() < 1e-12);
+        assert_eq!(summary.risk.drawdown_peak, bars[0].timestamp);
+        assert_eq!(summary.risk.drawdown_trough, bars[1].timestamp);
+        assert_eq!(
+            summary.indicators,
+            vec![
+                ("sma_2".to_string(), Some(10.25)),
+                ("sma_30".to_string(), None),
+                ("sma_1d".to_string(), Some(12.5)),
+            ]
+        );
+        assert_eq!(Summary::from_bars("MSFT", &[], &options), None);
+
+        let mut compared = summary.clone();
+        compared.compare_to(&bars, &bars, &options);
+        assert_eq!(compared.fields().len(), summary.fields().len() + 5);
+        assert_eq!(compared.benchmark.unwrap().beta, Some(1.0));
+
+        let mut with_events = summary.clone();
+        let events = [Event {
+            timestamp: bars[1].timestamp,
+            kind: crate::model::EventKind::Dividend(0.8),
+        }];
+        with_events.add_events(&bars, &events);
+        assert_eq!(with_events.fields().len(), summary.fields().len() + 3);
+        assert_eq!(with_events.dividends.unwrap().dividends, 0.8);
+    }
+
+    #[test]
+    fn test_price_field() {
+        let mut bars = vec![bar(1, 10.0), bar(2, 8.0), bar(3, 12.5)];
+        bars[0].close = 20.0;
+        bars[0].high = 22.0;
+        bars[0].low = 18.0;
+        bars[2].low = 11.0;
+        let options = |price_field| SummaryOptions {
+            price_field,
+            ..SummaryOptions::default()
+        };
+
+        let traded = Summary::from_bars("MSFT", &bars, &options(PriceField::Close)).unwrap();
+        assert_eq!(traded.max, 20.0);
+        assert_eq!(traded.high, 22.0);
+        let typical = series_rows("MSFT", &bars, &options(PriceField::Typical));
+        assert_eq!(typical[0].price, 20.0);
+        assert_eq!(typical[2].price, 12.0);
+        assert_eq!("High".parse(), Ok(PriceField::High));
+        assert!("median".parse::<PriceField>().is_err());
+    }
+
+    #[test]
+    fn test_series_rows() {
+        let bars = vec![bar(1, 10.0), bar(2, 8.0), bar(3, 12.5)];
+        let options = SummaryOptions {
+            indicators: vec![Indicator::Sma(Window::Bars(2)), Indicator::Ema(3)],
+            ..SummaryOptions::default()
+        };
+        let rows = series_rows("MSFT", &bars, &options);
+
+        let prices: Vec<f64> = rows.iter().map(|r| r.price).collect();
+        assert_eq!(prices, vec![10.0, 8.0, 12.5]);
+        assert_eq!(
+            rows[0].indicators,
+            vec![("sma_2".to_string(), None), ("ema_3".to_string(), None)]
+        );
+        assert_eq!(
+            rows[2].indicators,
+            vec![
+                ("sma_2".to_string(), Some(10.25)),
+                ("ema_3".to_string(), Some(10.166666666666666))
+            ]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//!
+//! Continuously re-polls a provider and reports the symbols whose quotes changed.
+//!
+
+use crate::model::{Bar, BarInterval};
+use crate::provider::{ProviderResult, QuoteProvider};
+use crate::summary::{Summary, SummaryOptions};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+///
+/// A source of the current time that can also wait, so watch loops can run against a fake clock.
+///
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+    fn sleep(&self, duration: Duration);
+}
+
+///
+/// The wall clock.
+///
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+///
+/// Parses a polling interval such as `30s`, `5m` or `1h`. A plain number is read as seconds.
+///
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid interval '{}'", s))?;
+    let secs = match unit {
+        "" | "s" => value,
+        "m" => value * 60,
+        "h" => value * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid interval unit '{}', expected s, m or h",
+                unit
+            ))
+        }
+    };
+    if secs == 0 {
+        return Err("the interval has to be positive".to_string());
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+///
+/// Keeps the bars fetched so far for every symbol and only requests the tail on each poll.
+///
+pub struct Watcher<'a> {
+    provider: &'a dyn QuoteProvider,
+    symbols: Vec<String>,
+    from: DateTime<Utc>,
+    options: SummaryOptions,
+    benchmark: Option<String>,
+    interval: Option<BarInterval>,
+    history: HashMap<String, Vec<Bar>>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(
+        provider: &'a dyn QuoteProvider,
+        symbols: Vec<String>,
+        from: DateTime<Utc>,
+        options: SummaryOptions,
+    ) -> Self {
+        Watcher {
+            provider,
+            symbols,
+            from,
+            options,
+            benchmark: None,
+            interval: None,
+            history: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Compares every summary to `symbol`, which is polled along with the watched symbols.
+    ///
+    pub fn with_benchmark(mut self, symbol: String) -> Self {
+        self.benchmark = Some(symbol);
+        self
+    }
+
+    ///
+    /// Polls bars of `interval` instead of the provider's own bars.
+    ///
+    pub fn with_interval(mut self, interval: BarInterval) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    ///
+    /// The bars of `symbol` received so far.
+    ///
+    pub fn bars(&self, symbol: &str) -> &[Bar] {
+        self.history.get(symbol).map_or(&[], Vec::as_slice)
+    }
+
+    ///
+    /// Fetches the bars of `symbol` from its last known bar up to `now`.
+    ///
+    /// # Returns
+    /// Whether a new or revised bar was received.
+    ///
+    fn refresh(&mut self, symbol: &str, now: DateTime<Utc>) -> ProviderResult<bool> {
+        let bars = self.history.entry(symbol.to_string()).or_default();
+        // re-fetch the last known bar too, it may have been incomplete
+        let start = bars.last().map_or(self.from, |b| b.timestamp);
+
+        let fresh = match self.interval {
+            Some(interval) => self.provider.fetch_bars(symbol, start, now, interval)?,
+            None => self.provider.fetch_history(symbol, start, now)?,
+        };
+        let previous_last = bars.last().cloned();
+        bars.retain(|b| b.timestamp < start);
+        bars.extend(fresh);
+        Ok(bars.last() != previous_last.as_ref())
+    }
+
+    ///
+    /// Fetches the bars up to `now` that have not been seen yet.
+    ///
+    /// # Returns
+    /// An updated summary for every symbol that received a new or revised bar, and the errors
+    /// of the symbols that could not be fetched. A new benchmark bar updates every symbol.
+    ///
+    pub fn poll(&mut self, now: DateTime<Utc>) -> Vec<(String, ProviderResult<Summary>)> {
+        let mut updates = Vec::new();
+        let mut benchmark_changed = false;
+        if let Some(benchmark) = self.benchmark.clone() {
+            match self.refresh(&benchmark, now) {
+                Ok(changed) => benchmark_changed = changed,
+                Err(e) => updates.push((benchmark, Err(e))),
+            }
+        }
+
+        for symbol in self.symbols.clone() {
+            match self.refresh(&symbol, now) {
+                Ok(changed) if changed || benchmark_changed => {
+                    let bars = &self.history[&symbol];
+                    if let Some(mut summary) = Summary::from_bars(&symbol, bars, &self.options) {
+                        let benchmark = self.benchmark.as_ref().and_then(|b| self.history.get(b));
+                        if let Some(benchmark) = benchmark {
+                            summary.compare_to(bars, benchmark, &self.options);
+                        }
+                        updates.push((symbol, Ok(summary)));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => updates.push((symbol, Err(e))),
+            }
+        }
+        updates
+    }
+
+    ///
+    /// Polls every `interval` and hands each update to `emit`. Stops after `ticks` polls, or never if `None`.
+    ///
+    pub fn run<C, F>(&mut self, clock: &C, interval: Duration, ticks: Option<usize>, mut emit: F)
+    where
+        C: Clock,
+        F: FnMut(&str, ProviderResult<Summary>),
+    {
+        let mut tick = 0;
+        loop {
+            for (symbol, update) in self.poll(clock.now()) {
+                emit(&symbol, update);
+            }
+            tick += 1;
+            if ticks.is_some_and(|t| tick >= t) {
+                break;
+            }
+            clock.sleep(interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::csv_file::CsvProvider;
+    use std::cell::Cell;
+
+    struct FakeClock {
+        now: Cell<DateTime<Utc>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now
+                .set(self.now.get() + chrono::Duration::from_std(duration).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("m").is_err());
+    }
+
+    #[test]
+    fn test_watcher_emits_new_bars_only() {
+        let path =
+            std::env::temp_dir().join(format!("stock-tracker-watch-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "symbol,timestamp,open,high,low,close\n\
+             MSFT,2021-01-01T10:00:00Z,1,1,1,10\n\
+             MSFT,2021-01-01T11:00:00Z,1,1,1,12\n\
+             MSFT,2021-01-01T13:00:00Z,1,1,1,9\n\
+             IBM,2021-01-01T10:00:00Z,1,1,1,5\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let clock = FakeClock {
+            now: Cell::new(Utc.with_ymd_and_hms(2021, 1, 1, 10, 30, 0).unwrap()),
+        };
+        let mut watcher = Watcher::new(
+            &provider,
+            vec!["MSFT".to_string(), "IBM".to_string()],
+            start,
+            SummaryOptions::default(),
+        );
+
+        let mut emitted = Vec::new();
+        watcher.run(&clock, Duration::from_secs(3600), Some(4), |_, update| {
+            let summary = update.unwrap();
+            emitted.push((summary.symbol, summary.price, summary.min, summary.max))
+        });
+
+        // 10:30 both symbols, 11:30 MSFT's second bar, 12:30 nothing new, 13:30 MSFT's third bar
+        assert_eq!(
+            emitted,
+            vec![
+                ("MSFT".to_string(), 10.0, 10.0, 10.0),
+                ("IBM".to_string(), 5.0, 5.0, 5.0),
+                ("MSFT".to_string(), 12.0, 10.0, 12.0),
+                ("MSFT".to_string(), 9.0, 9.0, 12.0),
+            ]
+        );
+        assert_eq!(watcher.bars("MSFT").len(), 3);
+        assert!(watcher.bars("GOOG").is_empty());
+    }
+
+    #[test]
+    fn test_watcher_updates_on_benchmark_bars() {
+        let path = std::env::temp_dir().join(format!(
+            "stock-tracker-watch-benchmark-{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "symbol,timestamp,open,high,low,close\n\
+             MSFT,2021-01-01T10:00:00Z,1,1,1,10\n\
+             MSFT,2021-01-01T11:00:00Z,1,1,1,12\n\
+             SPY,2021-01-01T10:00:00Z,1,1,1,100\n\
+             SPY,2021-01-01T11:00:00Z,1,1,1,110\n\
+             SPY,2021-01-01T12:00:00Z,1,1,1,99\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let clock = FakeClock {
+            now: Cell::new(Utc.with_ymd_and_hms(2021, 1, 1, 11, 30, 0).unwrap()),
+        };
+        let mut watcher = Watcher::new(
+            &provider,
+            vec!["MSFT".to_string()],
+            start,
+            SummaryOptions::default(),
+        )
+        .with_benchmark("SPY".to_string());
+
+        let mut emitted = Vec::new();
+        watcher.run(&clock, Duration::from_secs(3600), Some(2), |_, update| {
+            let benchmark = update.unwrap().benchmark.unwrap();
+            emitted.push(benchmark.relative_performance.unwrap());
+        });
+
+        // 11:30 both moved, 12:30 only the benchmark fell but MSFT is compared again
+        assert_eq!(emitted.len(), 2);
+        assert!((emitted[0] - (1.2 / 1.1 - 1.0)).abs() < 1e-12);
+        assert!((emitted[1] - emitted[0]).abs() < 1e-12);
+    }
+}